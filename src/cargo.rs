@@ -0,0 +1,167 @@
+//! Invocation of cargo, with the options shared by all of its subcommands.
+
+use std::{env, ffi::OsString, process::Command};
+
+use cargo_metadata::MetadataCommand;
+use clap::Args;
+
+#[derive(Args, Default, Debug, Clone)]
+/// Options forwarded to every cargo invocation.
+pub struct CargoOptions {
+    #[clap(long, action)]
+    /// Run without accessing the network.
+    pub offline: bool,
+    #[clap(long, action)]
+    /// Require Cargo.lock to be up-to-date.
+    pub locked: bool,
+    #[clap(long, action)]
+    /// Require Cargo.lock and cache to be up-to-date.
+    pub frozen: bool,
+    #[clap(long, value_name("KEY=VALUE"))]
+    /// Override a cargo configuration value.
+    pub config: Vec<String>,
+    #[clap(short('Z'), value_name("FLAG"))]
+    /// Unstable (nightly-only) flags to cargo.
+    pub unstable_flags: Vec<String>,
+    #[clap(short, long, value_name("N"))]
+    /// Number of parallel jobs, defaults to the number of CPUs.
+    pub jobs: Option<String>,
+    #[clap(long, action, conflicts_with("profile"))]
+    /// Build the documentation in release mode.
+    pub release: bool,
+    #[clap(long, value_name("PROFILE-NAME"))]
+    /// Build the documentation with the specified profile.
+    pub profile: Option<String>,
+    #[clap(short, long, action)]
+    /// Do not print cargo log messages.
+    pub quiet: bool,
+    #[clap(last(true), value_name("CARGO_ARGS"))]
+    /// Extra arguments forwarded to `cargo doc`.
+    pub args: Vec<String>,
+    #[clap(skip)]
+    /// Flags passed to rustdoc in addition to the ones of the `RUSTDOCFLAGS` environment variable.
+    pub rustdoc_flags: Vec<String>,
+    #[clap(skip)]
+    /// Flags passed to rustc in addition to the ones of the `RUSTFLAGS` environment variable.
+    pub rustc_flags: Vec<String>
+}
+
+/// Return the value of a flags environment variable with the given flags appended, if there are
+/// any.
+fn extend_env_flags(var: &str, flags: &[String]) -> Option<String> {
+    if flags.is_empty() {
+        return None;
+    }
+    let mut value = env::var(var).map(|flags| flags + " ").unwrap_or_default();
+    value.push_str(&flags.join(" "));
+    Some(value)
+}
+
+impl CargoOptions {
+    /// Return the cargo executable: the one that invoked us when we are run as a cargo subcommand,
+    /// and the one found in the `PATH` otherwise.
+    pub fn program() -> OsString {
+        env::var_os("CARGO").unwrap_or_else(|| "cargo".into())
+    }
+
+    /// Return the arguments understood by every cargo subcommand.
+    fn global_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.offline {
+            args.push("--offline".to_owned());
+        }
+        if self.locked {
+            args.push("--locked".to_owned());
+        }
+        if self.frozen {
+            args.push("--frozen".to_owned());
+        }
+        for config in &self.config {
+            args.extend_from_slice(&["--config".to_owned(), config.clone()]);
+        }
+        for flag in &self.unstable_flags {
+            args.extend_from_slice(&["-Z".to_owned(), flag.clone()]);
+        }
+        args
+    }
+
+    /// Generate the args for a build subcommand such as `cargo doc`.
+    pub fn build_args(&self) -> Vec<String> {
+        let mut args = self.global_args();
+        if let Some(jobs) = &self.jobs {
+            args.extend_from_slice(&["--jobs".to_owned(), jobs.clone()]);
+        }
+        if self.release {
+            args.push("--release".to_owned());
+        }
+        if let Some(profile) = &self.profile {
+            args.extend_from_slice(&["--profile".to_owned(), profile.clone()]);
+        }
+        if self.quiet {
+            args.push("--quiet".to_owned());
+        }
+        args.extend(self.args.iter().cloned());
+        args
+    }
+
+    /// Return a command running the given cargo subcommand, with the extra rustdoc and rustc
+    /// flags. The subcommand arguments are left to the caller.
+    pub fn command(&self, subcommand: &str) -> Command {
+        let mut command = Command::new(Self::program());
+        command.arg(subcommand);
+        if let Some(rustdoc_flags) = extend_env_flags("RUSTDOCFLAGS", &self.rustdoc_flags) {
+            command.env("RUSTDOCFLAGS", rustdoc_flags);
+        }
+        if let Some(rustc_flags) = extend_env_flags("RUSTFLAGS", &self.rustc_flags) {
+            command.env("RUSTFLAGS", rustc_flags);
+        }
+        command
+    }
+
+    /// Return the `cargo metadata` command for the given manifest.
+    pub fn metadata(&self, manifest: &clap_cargo::Manifest) -> MetadataCommand {
+        let mut metadata = manifest.metadata();
+        metadata.cargo_path(Self::program()).other_options(self.global_args());
+        metadata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::CargoOptions;
+
+    #[test]
+    fn test_global_args_are_shared() {
+        let options = CargoOptions {
+            offline: true,
+            frozen: true,
+            config: vec!["net.retry=5".to_owned()],
+            unstable_flags: vec!["rustdoc-map".to_owned()],
+            jobs: Some("4".to_owned()),
+            quiet: true,
+            args: vec!["--timings".to_owned()],
+            ..Default::default()
+        };
+        let global_args = ["--offline", "--frozen", "--config", "net.retry=5", "-Z", "rustdoc-map"];
+        assert_eq!(options.global_args(), global_args);
+        assert_eq!(options.build_args(), [&global_args[..], &["--jobs", "4", "--quiet", "--timings"]].concat());
+        assert_eq!(
+            options.metadata(&clap_cargo::Manifest::default()).cargo_command().get_args().collect::<Vec<_>>(),
+            ["metadata", "--format-version", "1", "--offline", "--frozen", "--config", "net.retry=5", "-Z", "rustdoc-map"]
+        );
+    }
+
+    #[test]
+    fn test_command_extra_flags() {
+        let options = CargoOptions {
+            rustdoc_flags: vec!["--cfg".to_owned(), "docsrs".to_owned()],
+            ..Default::default()
+        };
+        let command = options.command("doc");
+        let rustdoc_flags = command.get_envs().find(|(var, _)| *var == "RUSTDOCFLAGS").and_then(|(_, value)| value);
+        assert!(rustdoc_flags.is_some_and(|flags| flags.to_string_lossy().ends_with("--cfg docsrs")));
+        assert!(command.get_envs().all(|(var, _)| var != OsStr::new("RUSTFLAGS")));
+    }
+}
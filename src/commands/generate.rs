@@ -1,57 +1,242 @@
 //! Implementation of the `docset` subcommand.
 
-use crate::{error::*, io::*, DocsetParams};
+use crate::{config, docs_rs, error::*, io::*, DocsetParams, EntryNames, EntrySource, Reexports, Split};
 
-use cargo_metadata::Metadata;
+use cargo_metadata::{Metadata, Package, PackageId};
 use derive_more::Constructor;
+use cargo_doc::DocumentedCrates;
+use contribution::{Author, Contribution};
+use external::ExternalCrates;
 use rusqlite::{params, Connection};
 use snafu::{ensure, ResultExt};
 
+mod archive;
+mod cargo_doc;
+mod contribution;
+mod external;
+mod feed;
+mod html;
+mod implementors;
+mod merge;
+mod rustdoc_json;
+mod search_index;
+mod shared;
+mod tarix;
+mod toc;
+
 use std::{
     borrow::ToOwned,
+    collections::{HashMap, HashSet},
+    env::current_dir,
     ffi::OsStr,
     fmt::Display,
-    fs::{copy, create_dir_all, read_dir, remove_dir_all, File},
+    fs::{create_dir_all, read, read_dir, read_to_string, remove_dir_all, remove_file, write, File},
     io::{Write, BufReader, BufRead},
     path::{Path, PathBuf},
-    process::Command,
     result::Result as StdResult,
+    slice,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// The kind of an item indexed in the docset.
+///
+/// Variants follow the rustdoc item taxonomy, and are mapped to the closest Dash entry type when
+/// written to the index (see the `Display` implementation).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum EntryType {
+    Attribute, // i.e. attribute proc macro
     Constant,
+    Derive, // i.e. derive proc macro
     Enum,
+    Field,
+    ForeignType,
     Function,
+    Impl, // i.e. trait implementation on a type page
+    Implementor, // i.e. trait implementation on a trait page
+    Keyword,
     Macro,
+    Method,
     Module,
     Package, // i.e. crate
+    Primitive,
+    Section, // i.e. heading in the documentation of a page
+    Static,
     Struct,
     Trait,
-    Type //Union // Is this even implemented in Rust ?
+    TraitAlias,
+    Type,
+    Union,
+    Variant
+}
+
+impl EntryType {
+    /// Return the entry type corresponding to a rustdoc item page prefix, e.g. `struct` for
+    /// `struct.Foo.html`, if that page documents an item we want to index.
+    fn from_rustdoc_prefix(prefix: &str) -> Option<EntryType> {
+        match prefix {
+            "attr" => Some(EntryType::Attribute),
+            // Older rustdoc versions used `const` rather than `constant`.
+            "constant" | "const" => Some(EntryType::Constant),
+            "derive" => Some(EntryType::Derive),
+            "enum" => Some(EntryType::Enum),
+            "foreigntype" => Some(EntryType::ForeignType),
+            "fn" => Some(EntryType::Function),
+            "keyword" => Some(EntryType::Keyword),
+            "macro" => Some(EntryType::Macro),
+            "primitive" => Some(EntryType::Primitive),
+            "static" => Some(EntryType::Static),
+            "struct" => Some(EntryType::Struct),
+            "trait" => Some(EntryType::Trait),
+            "traitalias" => Some(EntryType::TraitAlias),
+            "type" => Some(EntryType::Type),
+            "union" => Some(EntryType::Union),
+            _ => None
+        }
+    }
+
+    /// Return the entry type of a member anchor prefix found in an item page, e.g. `method` for
+    /// `#method.push`.
+    fn from_rustdoc_member_prefix(prefix: &str) -> Option<EntryType> {
+        match prefix {
+            "associatedconstant" => Some(EntryType::Constant),
+            "associatedtype" => Some(EntryType::Type),
+            "method" | "tymethod" => Some(EntryType::Method),
+            "structfield" => Some(EntryType::Field),
+            "variant" => Some(EntryType::Variant),
+            _ => None
+        }
+    }
+
+    /// Whether pages documenting items of this type list members we can index.
+    fn has_members(&self) -> bool {
+        matches!(
+            self,
+            EntryType::Enum | EntryType::Primitive | EntryType::Struct | EntryType::Trait | EntryType::Union
+        )
+    }
 }
 
 impl Display for EntryType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> StdResult<(), std::fmt::Error> {
         match self {
+            EntryType::Attribute => write!(f, "Attribute"),
             EntryType::Constant => write!(f, "Constant"),
+            EntryType::Derive => write!(f, "Macro"),
             EntryType::Enum => write!(f, "Enum"),
+            EntryType::Field => write!(f, "Field"),
+            EntryType::ForeignType => write!(f, "Type"),
             EntryType::Function => write!(f, "Function"),
+            EntryType::Impl => write!(f, "Instance"),
+            EntryType::Implementor => write!(f, "Protocol"),
+            EntryType::Keyword => write!(f, "Keyword"),
             EntryType::Macro => write!(f, "Macro"),
+            EntryType::Method => write!(f, "Method"),
             EntryType::Module => write!(f, "Module"),
             EntryType::Package => write!(f, "Package"),
+            EntryType::Primitive => write!(f, "Type"),
+            EntryType::Section => write!(f, "Section"),
+            EntryType::Static => write!(f, "Global"),
             EntryType::Struct => write!(f, "Struct"),
             EntryType::Trait => write!(f, "Trait"),
-            EntryType::Type => write!(f, "Type")
+            EntryType::TraitAlias => write!(f, "Trait"),
+            EntryType::Type => write!(f, "Type"),
+            EntryType::Union => write!(f, "Union"),
+            EntryType::Variant => write!(f, "Variant")
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Constructor)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Constructor)]
 pub struct DocsetEntry {
     pub name: String,
     pub ty: EntryType,
-    pub path: PathBuf
+    pub path: PathBuf,
+    /// Anchor of the entry inside the page, for entries that do not have their own page.
+    pub fragment: Option<String>
+}
+
+/// Targets each entry is available on, for docsets merging the documentation of several targets.
+type EntryTargets = HashMap<DocsetEntry, Vec<String>>;
+
+impl DocsetEntry {
+    /// Return the value of the path column for this entry in the docset index.
+    fn index_path(&self) -> String {
+        let path = self.path.to_str().unwrap();
+        match &self.fragment {
+            Some(fragment) => format!("{}#{}", path, fragment),
+            None => path.to_owned()
+        }
+    }
+
+    /// Return the bare name of this entry and the module path it is found in, if any, e.g. `len`
+    /// and `my_crate::Foo` for `my_crate::Foo::len`.
+    fn short_name(&self) -> (&str, Option<&str>) {
+        if self.ty == EntryType::Section {
+            if let Some((module_path, heading)) = self.name.split_once(" - ") {
+                return (heading, Some(module_path));
+            }
+        }
+        // Impls and aliases have names which are not paths, and are kept as is.
+        if self.name.contains(' ') {
+            return (&self.name, self.path.iter().next().and_then(|c| c.to_str()));
+        }
+        match self.name.rsplit_once("::") {
+            Some((module_path, name)) => (name, Some(module_path)),
+            None => (&self.name, None)
+        }
+    }
+
+    /// Return the values of the name and path columns for this entry in the docset index. The
+    /// targets the entry is available on are only given for docsets merging several targets.
+    fn index_name_and_path(
+        &self,
+        entry_names: EntryNames,
+        crate_versions: &HashMap<String, String>,
+        targets: &[String]
+    ) -> (String, String) {
+        let (name, module_path, version) = match entry_names {
+            EntryNames::Qualified => (self.name.as_str(), None, None),
+            EntryNames::Short => {
+                let (name, module_path) = self.short_name();
+                let version = self
+                    .path
+                    .iter()
+                    .next()
+                    .and_then(|crate_dir| crate_versions.get(crate_dir.to_str()?));
+                (name, module_path, version.map(String::as_str))
+            }
+        };
+        let targets = (!targets.is_empty()).then(|| format!("[{}]", targets.join(", ")));
+        let description = [module_path, version, targets.as_deref()]
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if entry_names == EntryNames::Qualified && description.is_empty() {
+            return (self.name.clone(), self.index_path());
+        }
+        // The values are percent-encoded, as names such as `impl Add<u8> for u8` would otherwise
+        // be mistaken for the end of the tags.
+        let path = format!(
+            "<dash_entry_name={}><dash_entry_menuDescription={}>{}",
+            html::percent_encode(name),
+            html::percent_encode(&description),
+            self.index_path()
+        );
+        (name.to_owned(), path)
+    }
+}
+
+/// Return the versions of the crates documented in the workspace, keyed by the name of their
+/// documentation directory.
+fn get_crate_versions(cargo_metadata: &Metadata) -> HashMap<String, String> {
+    let mut crate_versions = HashMap::new();
+    for package in &cargo_metadata.packages {
+        for target in &package.targets {
+            crate_versions.insert(target.name.replace('-', "_"), package.version.to_string());
+        }
+    }
+    crate_versions
 }
 
 fn check_if_redirection(html_file: &mut File) -> bool {
@@ -75,6 +260,22 @@ fn check_if_redirection(html_file: &mut File) -> bool {
     file_contents.contains("<title>Redirection</title>")
 }
 
+/// Resolve a relative link of a page to a path relative to the rustdoc root directory, if it
+/// points to a page of the rustdoc directory.
+fn resolve_link<P1: AsRef<Path>, P2: AsRef<Path>>(
+    rustdoc_root_dir: P1,
+    file_path: P2,
+    target: &str
+) -> Option<PathBuf> {
+    let page_path = file_path.as_ref().strip_prefix(&rustdoc_root_dir).ok()?;
+    let resolved = html::resolve_href(page_path, target)?;
+    if rustdoc_root_dir.as_ref().join(&resolved).is_file() {
+        Some(resolved)
+    } else {
+        None
+    }
+}
+
 fn parse_docset_entry<P1: AsRef<Path>, P2: AsRef<Path>>(
     module_path: &Option<&str>,
     rustdoc_root_dir: P1,
@@ -84,6 +285,8 @@ fn parse_docset_entry<P1: AsRef<Path>, P2: AsRef<Path>>(
         let file_name = file_path.as_ref().file_name().unwrap().to_string_lossy();
         let mut file = File::open(file_path.as_ref())
             .expect("Could not open file");
+        // Redirection pages are written at the private definition path of re-exported items, or
+        // at legacy paths of items, and are not indexed.
         if check_if_redirection(&mut file) {
             return None;
         }
@@ -105,14 +308,16 @@ fn parse_docset_entry<P1: AsRef<Path>, P2: AsRef<Path>>(
                                 Some(DocsetEntry::new(
                                     mod_path.to_string(),
                                     EntryType::Module,
-                                    file_db_path
+                                    file_db_path,
+                                    None
                                 ))
                             } else {
                                 // Package entry
                                 Some(DocsetEntry::new(
                                     mod_path.to_string(),
                                     EntryType::Package,
-                                    file_db_path
+                                    file_db_path,
+                                    None
                                 ))
                             }
                         } else {
@@ -122,44 +327,14 @@ fn parse_docset_entry<P1: AsRef<Path>, P2: AsRef<Path>>(
                     _ => None
                 }
             }
-            3 => match parts[0] {
-                "const" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap(), parts[1]),
-                    EntryType::Constant,
-                    file_db_path
-                )),
-                "enum" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap(), parts[1]),
-                    EntryType::Enum,
-                    file_db_path
-                )),
-                "fn" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap(), parts[1]),
-                    EntryType::Function,
-                    file_db_path
-                )),
-                "macro" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap(), parts[1]),
-                    EntryType::Macro,
-                    file_db_path
-                )),
-                "trait" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap(), parts[1]),
-                    EntryType::Trait,
-                    file_db_path
-                )),
-                "struct" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap(), parts[1]),
-                    EntryType::Struct,
-                    file_db_path
-                )),
-                "type" => Some(DocsetEntry::new(
+            3 => EntryType::from_rustdoc_prefix(parts[0]).map(|ty| {
+                DocsetEntry::new(
                     format!("{}::{}", module_path.unwrap(), parts[1]),
-                    EntryType::Type,
-                    file_db_path
-                )),
-                _ => None
-            },
+                    ty,
+                    file_db_path,
+                    None
+                )
+            }),
             _ => None
         }
     } else {
@@ -167,15 +342,125 @@ fn parse_docset_entry<P1: AsRef<Path>, P2: AsRef<Path>>(
     }
 }
 
+/// Return the entry for a `#[doc(alias = "...")]` of the item documented by `entry`.
+fn alias_entry(alias: &str, entry: &DocsetEntry) -> DocsetEntry {
+    DocsetEntry::new(
+        format!("{} (→ {})", alias, entry.name),
+        entry.ty.clone(),
+        entry.path.clone(),
+        entry.fragment.clone()
+    )
+}
+
+/// Return the entry type and name of the member documented at a rustdoc anchor, e.g.
+/// `method.push`, if it is one.
+fn parse_member_anchor(id: &str) -> Option<(EntryType, &str)> {
+    let (prefix, name) = id.split_once('.')?;
+    // Nested anchors such as `variant.Foo.field.bar` are not indexed.
+    if name.is_empty() || name.contains('.') {
+        return None;
+    }
+    let ty = EntryType::from_rustdoc_member_prefix(prefix)?;
+    Some((ty, html::strip_disambiguator(name)))
+}
+
+/// Parse the member anchors (methods, fields, variants, associated items) of an item page, and
+/// return an entry for each of them.
+fn parse_member_entries<P: AsRef<Path>>(page_entry: &DocsetEntry, file_path: P) -> Result<Vec<DocsetEntry>> {
+    let contents = read_to_string(file_path).context(IoReadSnafu)?;
+    // Auto trait (`Send`, `Sync`, ...) and blanket implementations (`Into`, `Borrow`, ...) are
+    // repeated on every page, and only add noise to the index. They are always listed last.
+    let end = ["id=\"synthetic-implementations\"", "id=\"blanket-implementations\""]
+        .iter()
+        .filter_map(|section| contents.find(section))
+        .min();
+    let contents = match end {
+        Some(end) => &contents[..end],
+        None => &contents[..]
+    };
+
+    let mut entries = vec![];
+    for (tag_start, tag_name, id) in html::element_ids(contents) {
+        if let Some((ty, name)) = parse_member_anchor(id) {
+            entries.push(DocsetEntry::new(
+                format!("{}::{}", page_entry.name, name),
+                ty,
+                page_entry.path.clone(),
+                Some(id.to_owned())
+            ));
+        } else if id.starts_with("impl-") {
+            entries.extend(implementors::impl_entry(contents, tag_start, tag_name, id, page_entry));
+        }
+    }
+    Ok(entries)
+}
+
+/// Parse the headings of the documentation of a crate or module page, and return an entry for
+/// each of them. Headings are prefixed with the crate or module path, as many pages have the same
+/// sections, e.g. "Examples".
+fn parse_section_entries<P: AsRef<Path>>(page_entry: &DocsetEntry, file_path: P) -> Result<Vec<DocsetEntry>> {
+    let contents = read_to_string(file_path).context(IoReadSnafu)?;
+    let docblock = match html::docblock(&contents) {
+        Some(docblock) => docblock,
+        None => return Ok(vec![])
+    };
+
+    let mut entries = vec![];
+    for (tag_start, tag_name, id) in html::element_ids(docblock) {
+        if !matches!(tag_name, "h2" | "h3" | "h4" | "h5" | "h6") {
+            continue;
+        }
+        if let Some(heading) = html::element_text(docblock, tag_start, tag_name).filter(|h| !h.is_empty()) {
+            entries.push(DocsetEntry::new(
+                format!("{} - {}", page_entry.name, heading),
+                EntryType::Section,
+                page_entry.path.clone(),
+                Some(id.to_owned())
+            ));
+        }
+    }
+    Ok(entries)
+}
+
+/// Parse the "Re-exports" section of a crate or module page, and return an entry for each item it
+/// re-exports, named after its path in the crate or module and pointing to the page of the item.
+fn parse_reexport_entries<P1: AsRef<Path>, P2: AsRef<Path>>(
+    page_entry: &DocsetEntry,
+    rustdoc_root_dir: P1,
+    file_path: P2
+) -> Result<Vec<DocsetEntry>> {
+    let contents = read_to_string(&file_path).context(IoReadSnafu)?;
+    let mut entries = vec![];
+    for (name, kind, href) in html::reexports(&contents) {
+        let ty = match kind {
+            "mod" => Some(EntryType::Module),
+            kind => EntryType::from_rustdoc_prefix(kind)
+        };
+        // Items of crates which are not documented, e.g. the standard library, have no page.
+        let path = resolve_link(&rustdoc_root_dir, &file_path, href);
+        if let (Some(ty), Some(path)) = (ty, path) {
+            entries.push(DocsetEntry::new(format!("{}::{}", page_entry.name, name), ty, path, None));
+        }
+    }
+    Ok(entries)
+}
+
 const ROOT_SKIP_DIRS: &[&str] = &["src", "implementors"];
 
+/// Entries found while walking the rustdoc directory.
+#[derive(Debug, Default)]
+struct WalkedEntries {
+    entries: Vec<DocsetEntry>,
+    reexports: Vec<DocsetEntry>
+}
+
 fn recursive_walk(
     root_dir: &Path,
     cur_dir: &Path,
     module_path: Option<&str>
-) -> Result<Vec<DocsetEntry>> {
+) -> Result<WalkedEntries> {
     let dir = read_dir(cur_dir).context(IoReadSnafu)?;
-    let mut entries = vec![];
+    let mut walked = WalkedEntries::default();
     let mut subdir_entries = vec![];
 
     for dir_entry in dir {
@@ -194,17 +479,53 @@ fn recursive_walk(
                     Some(&subdir_module_path)
                 ));
             }
-        } else if let Some(entry) = parse_docset_entry(&module_path, root_dir, &dir_entry.path()) {
-            entries.push(entry);
+        } else {
+            if let Some(entry) = parse_docset_entry(&module_path, root_dir, dir_entry.path()) {
+                if entry.ty.has_members() {
+                    walked.entries.extend(parse_member_entries(&entry, dir_entry.path())?);
+                }
+                if matches!(entry.ty, EntryType::Package | EntryType::Module) {
+                    walked.entries.extend(parse_section_entries(&entry, dir_entry.path())?);
+                    walked.reexports.extend(parse_reexport_entries(&entry, root_dir, dir_entry.path())?);
+                }
+                walked.entries.push(entry);
+            }
         }
     }
     for v in subdir_entries {
-        entries.extend(v?);
+        let v = v?;
+        walked.entries.extend(v.entries);
+        walked.reexports.extend(v.reexports);
+    }
+    Ok(walked)
+}
+
+/// Walk the rustdoc directory and return the docset entries inferred from the file names.
+fn walk_rustdoc_dir(rustdoc_root_dir: &Path, reexports: Reexports) -> Result<Vec<DocsetEntry>> {
+    let WalkedEntries { mut entries, reexports: reexport_entries } =
+        recursive_walk(rustdoc_root_dir, rustdoc_root_dir, None)?;
+    entries.extend(implementors::implementor_entries(rustdoc_root_dir)?);
+    match reexports {
+        Reexports::Canonical => (),
+        Reexports::Reexport => {
+            // Re-exported items are only indexed under their re-export path, but their members
+            // keep their canonical path.
+            let reexported_pages = reexport_entries.iter().map(|e| &e.path).collect::<HashSet<_>>();
+            entries.retain(|e| e.fragment.is_some() || !reexported_pages.contains(&e.path));
+            entries.extend(reexport_entries);
+        }
+        Reexports::Both => entries.extend(reexport_entries)
     }
     Ok(entries)
 }
 
-fn generate_sqlite_index<P: AsRef<Path>>(docset_dir: P, entries: Vec<DocsetEntry>) -> Result<()> {
+fn generate_sqlite_index<P: AsRef<Path>>(
+    docset_dir: P,
+    entries: Vec<DocsetEntry>,
+    entry_names: EntryNames,
+    crate_versions: &HashMap<String, String>,
+    entry_targets: &EntryTargets
+) -> Result<()> {
     let mut conn_path = docset_dir.as_ref().to_owned();
     conn_path.push("Contents");
     conn_path.push("Resources");
@@ -223,10 +544,12 @@ fn generate_sqlite_index<P: AsRef<Path>>(docset_dir: P, entries: Vec<DocsetEntry
             .prepare("INSERT INTO searchIndex (name, type, path) VALUES (?1, ?2, ?3)")
             .context(SqliteSnafu)?;
         for entry in entries {
+            let targets = entry_targets.get(&entry).map(Vec::as_slice).unwrap_or_default();
+            let (name, path) = entry.index_name_and_path(entry_names, crate_versions, targets);
             stmt.execute([
-                entry.name,
+                name,
                 entry.ty.to_string(),
-                entry.path.to_str().unwrap().to_owned()
+                path
             ])
             .context(SqliteSnafu)?;
         }
@@ -235,27 +558,68 @@ fn generate_sqlite_index<P: AsRef<Path>>(docset_dir: P, entries: Vec<DocsetEntry
     Ok(())
 }
 
-fn copy_dir_recursive<Ps: AsRef<Path>, Pd: AsRef<Path>>(src: Ps, dst: Pd) -> Result<()> {
-    create_dir_all(&dst).context(IoWriteSnafu)?;
-    for entry in read_dir(&src).context(IoReadSnafu)? {
-        let entry = entry.context(IoWriteSnafu)?.path();
-        if entry.is_dir() {
-            let mut dst_dir = dst.as_ref().to_owned();
-            dst_dir.push(entry.strip_prefix(&src).unwrap());
-            copy_dir_recursive(entry, dst_dir)?;
-        } else if entry.is_file() {
-            let mut dst_file = dst.as_ref().to_owned();
-            dst_file.push(entry.file_name().unwrap());
-            copy(entry, dst_file).context(IoWriteSnafu)?;
+/// Write a file of the rustdoc directory, found at `path` relative to it, to `dst`. HTML pages are
+/// rewritten on the fly by `rewrite_html`, and get the table of contents anchors. The scripts
+/// shared by the crates only keep the parts of the crates matching `keep_crate`.
+fn write_doc_file(
+    dst: &Path,
+    path: &Path,
+    contents: Vec<u8>,
+    keep_crate: &dyn Fn(&str) -> bool,
+    rewrite_html: &dyn Fn(&Path, &str) -> String
+) -> Result<()> {
+    if path.extension() == Some(OsStr::new("html")) {
+        let html = rewrite_html(path, &String::from_utf8_lossy(&contents));
+        write(dst, toc::insert_dash_anchors(&html)).context(IoWriteSnafu)
+    } else if shared::is_shared_script(path) {
+        write(dst, shared::filter_crates(path, &String::from_utf8_lossy(&contents), keep_crate)).context(IoWriteSnafu)
+    } else {
+        write(dst, contents).context(IoWriteSnafu)
+    }
+}
+
+/// Copy the `src` directory to `dst`, except for the files and directories matching `skip`. The
+/// HTML pages are rewritten by `rewrite_html`. The closures are given paths relative to `src`.
+fn copy_dir_recursive(
+    src: &Path,
+    dst: &Path,
+    skip: &dyn Fn(&Path) -> bool,
+    keep_crate: &dyn Fn(&str) -> bool,
+    rewrite_html: &dyn Fn(&Path, &str) -> String
+) -> Result<()> {
+    fn copy_dir(
+        root: &Path,
+        dir: &Path,
+        dst: &Path,
+        skip: &dyn Fn(&Path) -> bool,
+        keep_crate: &dyn Fn(&str) -> bool,
+        rewrite_html: &dyn Fn(&Path, &str) -> String
+    ) -> Result<()> {
+        create_dir_all(dst.join(dir)).context(IoWriteSnafu)?;
+        for entry in read_dir(root.join(dir)).context(IoReadSnafu)? {
+            let entry = entry.context(IoWriteSnafu)?.path();
+            let path = entry.strip_prefix(root).unwrap();
+            if skip(path) {
+                continue;
+            }
+            if entry.is_dir() {
+                copy_dir(root, path, dst, skip, keep_crate, rewrite_html)?;
+            } else if entry.is_file() {
+                let contents = read(&entry).context(IoReadSnafu)?;
+                write_doc_file(&dst.join(path), path, contents, keep_crate, rewrite_html)?;
+            }
         }
+        Ok(())
     }
-    Ok(())
+
+    copy_dir(src, Path::new(""), dst, skip, keep_crate, rewrite_html)
 }
 
 fn write_metadata<P: AsRef<Path>>(
     docset_root_dir: P,
     docset_name: &str,
     index_package: Option<String>,
+    identifier: Option<String>,
     platform_family: Option<String>
 ) -> Result<()> {
     let mut info_plist_path = docset_root_dir.as_ref().to_owned();
@@ -272,11 +636,11 @@ fn write_metadata<P: AsRef<Path>>(
     } else {
         String::new()
     };
-    let identifier_entry = if let Some(platform_family) = &platform_family {
+    let identifier_entry = if let Some(identifier) = &identifier {
         format!(
             "<key>CFBundleIdentifier</key>
                     <string>{}</string>",
-            platform_family
+            identifier
         )
     } else {
         String::new()
@@ -305,6 +669,8 @@ fn write_metadata<P: AsRef<Path>>(
             {}
             <key>isDashDocset</key>
                 <true/>
+            <key>DashDocSetFamily</key>
+                <string>dashtoc</string>
             <key>isJavaScriptEnabled</key>
                 <true/>
         </dict>
@@ -321,9 +687,22 @@ fn get_workspace_name(metadata: &Metadata) -> String {
         .to_owned()
 }
 
+/// Return the name of the binary or example that was selected, if a single one was and nothing else
+/// is documented.
+fn get_selected_bin(cfg: &DocsetParams) -> Option<&str> {
+    if cfg.lib || cfg.bins || cfg.examples {
+        return None;
+    }
+    match (cfg.bin.as_slice(), cfg.example.as_slice()) {
+        ([bin], []) | ([], [bin]) => Some(bin),
+        _ => None
+    }
+}
+
 /// Determine the name we will use for the generated docset.
 /// If a name was provided on the command line, we use this one.
 /// If no name was provided:
+///   * If a single binary or example was requested, use this one.
 ///   * If a single package was requested, use this one.
 ///   * Otherwise, if there is a workspace root package and we have been asked to generate
 ///     documentation for it, use this one.
@@ -332,6 +711,9 @@ fn get_docset_name(cfg: &DocsetParams, metadata: &Metadata) -> String {
     if let Some(docset_name) = &cfg.docset_name {
         return docset_name.to_owned();
     }
+    if let Some(bin) = get_selected_bin(cfg) {
+        return bin.to_owned();
+    }
 
     let (included, _excluded) = cfg.workspace.partition_packages(metadata);
 
@@ -363,11 +745,34 @@ fn get_docset_index(cfg: &DocsetParams, metadata: &Metadata) -> Option<String> {
     if cfg.docset_index.is_some() {
         return cfg.docset_index.clone();
     }
-
-    match (cfg.workspace.all, cfg.workspace.package.len()) {
-        (false, 1) => Some(cfg.workspace.package[0].to_owned()),
-        _ => metadata.root_package().map(|p| p.name.to_owned())
+    // The documentation of binaries is in a directory named after their crate name.
+    if let Some(bin) = get_selected_bin(cfg) {
+        return Some(bin.replace('-', "_"));
     }
+
+    let package = match (cfg.workspace.all, cfg.workspace.package.len()) {
+        (false, 1) => {
+            let name = &cfg.workspace.package[0];
+            match metadata.workspace_packages().into_iter().find(|p| &p.name == name) {
+                Some(package) => package,
+                None => return Some(name.replace('-', "_"))
+            }
+        }
+        _ => metadata.root_package()?
+    };
+    Some(get_package_index_dir(package))
+}
+
+/// Return the directory of the documentation of a package: the one of its library, or of its
+/// binary if it has no library and a single binary.
+fn get_package_index_dir(package: &Package) -> String {
+    let lib = package.targets.iter().find(|target| external::is_lib_target(target));
+    let mut bins = package.targets.iter().filter(|target| target.kind.iter().any(|kind| kind == "bin"));
+    let target = lib.or_else(|| match (bins.next(), bins.next()) {
+        (Some(bin), None) => Some(bin),
+        _ => None
+    });
+    target.map_or(&package.name, |target| &target.name).replace('-', "_")
 }
 
 /// Return the keyword that should be used for the docset platform family, if any.
@@ -376,6 +781,9 @@ fn get_docset_platform_family(cfg: &DocsetParams, metadata: &Metadata) -> Option
     if let Some(platform_family) = &cfg.platform_family {
         return Some(platform_family.to_owned());
     }
+    if let Some(bin) = get_selected_bin(cfg) {
+        return Some(bin.to_owned());
+    }
 
     match (cfg.workspace.all, cfg.workspace.package.len()) {
         (false, 1) => Some(cfg.workspace.package[0].to_owned()),
@@ -383,7 +791,65 @@ fn get_docset_platform_family(cfg: &DocsetParams, metadata: &Metadata) -> Option
     }
 }
 
-pub fn generate_docset(cfg: DocsetParams) -> Result<()> {
+/// Return the description of the contribution of a docset of the given package to the Dash
+/// user-contributed docsets, using the package metadata for the settings which were not given.
+fn get_contribution<'a>(cfg: &'a DocsetParams, package: &'a Package, name: &'a str, version: &'a str) -> Contribution<'a> {
+    // Authors are usually formatted as `Name <email>`.
+    let author_name = cfg.contribution_author.clone().or_else(|| {
+        package
+            .authors
+            .first()
+            .map(|author| author.split('<').next().unwrap_or_default().trim().to_owned())
+            .filter(|name| !name.is_empty())
+    });
+    let author_link = cfg
+        .contribution_author_link
+        .clone()
+        .or_else(|| package.repository.clone())
+        .or_else(|| package.homepage.clone());
+    Contribution {
+        name,
+        description: package.description.as_deref(),
+        version,
+        author: Author {
+            name: author_name.unwrap_or_default(),
+            link: author_link.unwrap_or_default()
+        },
+        aliases: &cfg.contribution_alias,
+        icon_dir: package.manifest_path.parent().map(|dir| dir.as_std_path())
+    }
+}
+
+/// Name of the target directory used to build the rustdoc JSON output, relative to the target
+/// directory. Using a separate directory prevents the JSON build from overwriting the HTML one.
+const RUSTDOC_JSON_TARGET_DIR: &str = "docset-rustdoc-json";
+
+/// Run `cargo doc` a second time to generate the rustdoc JSON output in the given target
+/// directory.
+fn generate_rustdoc_json(cfg: &DocsetParams, json_target_dir: &Path) -> Result<()> {
+    println!("Running 'cargo doc' with the rustdoc JSON output format...");
+    let mut json_cfg = cfg.clone();
+    json_cfg.target_dir = Some(json_target_dir.to_owned());
+    json_cfg
+        .cargo
+        .rustdoc_flags
+        .extend(["-Z", "unstable-options", "--output-format", "json"].map(ToOwned::to_owned));
+    let cargo_doc_result = json_cfg
+        .cargo
+        .command("doc")
+        .args(json_cfg.clone().into_args())
+        .status()
+        .context(SpawnSnafu)?;
+    if !cargo_doc_result.success() {
+        return CargoDocSnafu {
+            code: cargo_doc_result.code()
+        }
+        .fail();
+    }
+    Ok(())
+}
+
+pub fn generate_docset(mut cfg: DocsetParams) -> Result<()> {
     // Step 1: generate rustdoc
     // Figure out for which crate to build the doc and invoke cargo doc.
     // If no crate is specified, run cargo doc for the current crate/workspace.
@@ -396,91 +862,948 @@ pub fn generate_docset(cfg: DocsetParams) -> Result<()> {
         );
     }
 
-    let cargo_metadata = cfg.manifest.metadata().exec().context(CargoMetadataSnafu)?;
+    let cargo_metadata = cfg.cargo.metadata(&cfg.manifest).exec().context(CargoMetadataSnafu)?;
+    config::apply_metadata_config(&mut cfg, &cargo_metadata)?;
+    if cfg.docs_rs {
+        docs_rs::apply_docs_rs_metadata(&mut cfg, &cargo_metadata)?;
+    }
+    if cfg.merge_targets {
+        ensure!(
+            cfg.target.len() > 1,
+            ArgsSnafu {
+                msg: "--merge-targets must be used with several --target"
+            }
+        );
+        ensure!(
+            cfg.split == Split::None,
+            ArgsSnafu {
+                msg: "--split cannot be used with --merge-targets"
+            }
+        );
+    }
+
+    let target_dir = match &cfg.target_dir {
+        Some(target_dir) => absolute_target_dir(target_dir)?,
+        None => cargo_metadata.target_directory.clone().into_std_path_buf()
+    };
 
-    // Clean the documentation directory if the user didn't explicitly ask not to clean it.
+    // Clean the documentation of the documented crates if the user didn't explicitly ask not to
+    // clean it. The documentation of the other crates, e.g. dependencies, is reused.
     if !cfg.no_clean {
-        println!("Running 'cargo clean --doc'...");
-        let mut cargo_clean_args = vec!["clean".to_owned()];
-        if let Some(ref manifest_path) = &cfg.manifest.manifest_path {
-            cargo_clean_args.push("--manifest-path".to_owned());
-            cargo_clean_args.push(manifest_path.to_string_lossy().to_string());
-        }
-        let cargo_clean_result = Command::new("cargo")
-            .args(cargo_clean_args)
-            .arg("--doc")
-            .status()
-            .context(SpawnSnafu)?;
-        if !cargo_clean_result.success() {
-            return CargoCleanSnafu {
-                code: cargo_clean_result.code()
-            }
-            .fail();
-        }
+        println!("Cleaning the documentation of the documented crates...");
+        clean_docs(&cfg, &cargo_metadata, &target_dir)?;
     }
     // Good to go, generate the documentation.
+    // Only the crates documented by this run are indexed, as other ones may have been left over
+    // by previous runs when the doc directory is not cleaned.
     println!("Running 'cargo doc'...");
-    let args = cfg.clone().into_args();
-    let cargo_doc_result = Command::new("cargo")
-        .arg("doc")
-        .args(args)
-        .status()
-        .context(SpawnSnafu)?;
-    if !cargo_doc_result.success() {
-        return CargoDocSnafu {
-            code: cargo_doc_result.code()
+    let mut documented = cargo_doc::run_cargo_doc(&cfg.cargo, cfg.clone().into_args())?;
+    let external = get_external_crates(&cfg, &cargo_metadata);
+    documented.exclude(&external);
+
+    // Step 2: generate the docsets from the html files in the doc directory of each target
+    if cfg.entry_source == EntrySource::RustdocJson {
+        generate_rustdoc_json(&cfg, &target_dir.join(RUSTDOC_JSON_TARGET_DIR))?;
+    }
+    // Cargo documents each target triple in its own directory, so each of them gets its own
+    // docset unless they are merged.
+    if cfg.merge_targets {
+        generate_target_docset(&cfg, &cargo_metadata, &documented, &external, &target_dir, &cfg.target)?;
+    } else if cfg.target.is_empty() {
+        generate_target_docset(&cfg, &cargo_metadata, &documented, &external, &target_dir, &[])?;
+    }
+    for target in cfg.target.iter().filter(|_| !cfg.merge_targets) {
+        generate_target_docset(&cfg, &cargo_metadata, &documented, &external, &target_dir, slice::from_ref(target))?;
+    }
+    Ok(())
+}
+
+/// Return the crates which are documented but left out of the docset: the dependencies beyond the
+/// selected depth, and the ones matching the excluded dependency patterns, as long as they are
+/// published on crates.io.
+fn get_external_crates(cfg: &DocsetParams, metadata: &Metadata) -> ExternalCrates {
+    let (included, _excluded) = cfg.workspace.partition_packages(metadata);
+    let mut left_out = HashSet::new();
+    if let Some(depth) = cfg.deps_depth {
+        left_out.extend(external::deps_beyond_depth(metadata, &included, depth).into_iter().map(|p| &p.id));
+    }
+    // Only the dependencies documented along with the selected packages are left out. Without a
+    // resolved dependency graph, every package outside of the workspace is one.
+    let levels = external::dependency_levels(metadata, &included);
+    let is_dependency = |package: &Package| {
+        !metadata.workspace_members.contains(&package.id)
+            && levels.as_ref().is_none_or(|levels| levels.contains_key(&package.id))
+    };
+    for package in metadata.packages.iter().filter(|p| is_dependency(p)) {
+        if cfg.exclude_deps.iter().any(|pattern| external::glob_match(pattern, &package.name)) {
+            left_out.insert(&package.id);
         }
-        .fail();
     }
 
-    // Step 2: iterate over all the html files in the doc directory and parse the filenames
-    let docset_name = get_docset_name(&cfg, &cargo_metadata);
-    let mut docset_root_dir = cfg
-        .target_dir
-        .clone()
-        .unwrap_or_else(|| cargo_metadata.target_directory.clone().into_std_path_buf());
-    let mut rustdoc_root_dir = docset_root_dir.clone();
-    rustdoc_root_dir.push("doc");
-    docset_root_dir.push("docset");
-    let platform_family = get_docset_platform_family(&cfg, &cargo_metadata);
-    docset_root_dir.push(
-        format!("{}.docset",
-            platform_family.clone()
-                .unwrap_or_else(|| get_workspace_name(&cargo_metadata))));
-    let entries = recursive_walk(&rustdoc_root_dir, &rustdoc_root_dir, None)?;
-
-    // Step 3: generate the SQLite database
-    // At this point, we need to start writing into the output docset directory, so create the
-    // hirerarchy, and clean it first if it already exists.
+    let mut external = ExternalCrates::default();
+    let mut unpublished = vec![];
+    for package in metadata.packages.iter().filter(|p| left_out.contains(&p.id)) {
+        if !external.insert(package) {
+            warn(&format!("{} is not published on crates.io, its documentation is kept in the docset.", package.name));
+            unpublished.push(&package.id);
+        }
+    }
+    for id in unpublished {
+        left_out.remove(id);
+    }
+    // Another version of an external package may be kept, and shares its crate directory.
+    for package in metadata.packages.iter().filter(|p| !left_out.contains(&p.id)) {
+        for target in &package.targets {
+            external.remove(&target.name.replace('-', "_"));
+        }
+    }
+    external
+}
+
+/// Resolve a target directory given on the command line against the current directory, as cargo
+/// does. The documented crates are reported by cargo with absolute paths, which the rustdoc
+/// directories of the target directory are compared to.
+fn absolute_target_dir(target_dir: &Path) -> Result<PathBuf> {
+    if target_dir.is_absolute() {
+        return Ok(target_dir.to_owned());
+    }
+    Ok(current_dir().context(CwdSnafu)?.join(target_dir))
+}
+
+/// Return the directory in which cargo writes the documentation built for the given target triple.
+fn get_rustdoc_root_dir(target_dir: &Path, target: Option<&str>) -> PathBuf {
+    match target {
+        Some(target) => target_dir.join(target).join("doc"),
+        None => target_dir.join("doc")
+    }
+}
+
+/// Remove the documentation of the given crates from a rustdoc directory, and their rustdoc JSON
+/// output if any.
+fn clean_crate_docs(rustdoc_root_dir: &Path, json_root_dir: &Path, crate_dirs: &HashSet<String>) -> Result<()> {
+    for crate_dir in crate_dirs {
+        for dir in [rustdoc_root_dir.join(crate_dir), rustdoc_root_dir.join("src").join(crate_dir)] {
+            if dir.is_dir() {
+                remove_dir_all(dir).context(IoWriteSnafu)?;
+            }
+        }
+        let json_file = json_root_dir.join(format!("{}.json", crate_dir));
+        if json_file.is_file() {
+            remove_file(json_file).context(IoWriteSnafu)?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove the documentation of the crates of the selected packages, in the doc directory of each
+/// selected target.
+fn clean_docs(cfg: &DocsetParams, metadata: &Metadata, target_dir: &Path) -> Result<()> {
+    let (included, _excluded) = cfg.workspace.partition_packages(metadata);
+    let crate_dirs = included
+        .iter()
+        .flat_map(|package| &package.targets)
+        .map(|target| target.name.replace('-', "_"))
+        .collect::<HashSet<_>>();
+
+    let mut targets = cfg.target.iter().map(|target| Some(target.as_str())).collect::<Vec<_>>();
+    if targets.is_empty() {
+        targets.push(None);
+    }
+    for target in targets {
+        clean_crate_docs(
+            &get_rustdoc_root_dir(target_dir, target),
+            &get_rustdoc_root_dir(&target_dir.join(RUSTDOC_JSON_TARGET_DIR), target),
+            &crate_dirs
+        )?;
+    }
+    Ok(())
+}
+
+/// Return the docset entries of the documentation built for the given target triple.
+fn get_entries(
+    cfg: &DocsetParams,
+    documented: &DocumentedCrates,
+    target_dir: &Path,
+    target: Option<&str>
+) -> Result<Vec<DocsetEntry>> {
+    let rustdoc_root_dir = get_rustdoc_root_dir(target_dir, target);
+    let mut entries = match cfg.entry_source {
+        EntrySource::Filenames => {
+            // Aliases are only recorded in the search index, whose format changes more often than
+            // the file names, so failing to parse it only leaves them out.
+            let mut entries = walk_rustdoc_dir(&rustdoc_root_dir, cfg.reexports)?;
+            match search_index::alias_entries(&rustdoc_root_dir) {
+                Ok(aliases) => entries.extend(aliases),
+                Err(e) => warn(&format!("the doc aliases were not indexed: {}", e))
+            }
+            entries
+        }
+        EntrySource::RustdocJson => {
+            let json_target_dir = target_dir.join(RUSTDOC_JSON_TARGET_DIR);
+            rustdoc_json::rustdoc_json_entries(&get_rustdoc_root_dir(&json_target_dir, target))?
+        }
+        EntrySource::SearchIndex => match search_index::search_index_entries(&rustdoc_root_dir)? {
+            Some(entries) => entries,
+            None => {
+                warn("no usable rustdoc search index was found, falling back to the file names.");
+                walk_rustdoc_dir(&rustdoc_root_dir, cfg.reexports)?
+            }
+        }
+    };
+    entries.retain(|entry| !documented.is_stale(&rustdoc_root_dir, &entry.path));
+    Ok(entries)
+}
+
+/// The names of a docset bundle, as displayed by docset browsers.
+#[derive(Debug, PartialEq, Eq)]
+struct DocsetBundle {
+    name: String,
+    file_stem: String,
+    index: Option<String>,
+    identifier: Option<String>,
+    platform_family: Option<String>,
+    /// The package the docset is named after, if any.
+    package: Option<PackageId>
+}
+
+impl DocsetBundle {
+    /// Name the bundle after the target triple its documentation was built for.
+    fn for_target(self, target: &str) -> DocsetBundle {
+        DocsetBundle {
+            name: format!("{} ({})", self.name, target),
+            file_stem: format!("{}-{}", self.file_stem, target),
+            identifier: self.identifier.map(|identifier| format!("{}-{}", identifier, target)),
+            ..self
+        }
+    }
+
+    /// Return the directory of the bundle, in the docset directory of the target directory.
+    fn root_dir(&self, target_dir: &Path) -> PathBuf {
+        target_dir.join("docset").join(format!("{}.docset", self.file_stem))
+    }
+}
+
+/// Create the hierarchy of a docset bundle, removing the previous one if it already exists.
+/// Returns the directory into which the documentation is copied.
+fn create_docset_dir(docset_root_dir: &Path) -> Result<PathBuf> {
     if docset_root_dir.exists() {
-        remove_dir_all(&docset_root_dir).context(IoWriteSnafu)?;
+        remove_dir_all(docset_root_dir).context(IoWriteSnafu)?;
     }
-    let mut docset_hierarchy = docset_root_dir.clone();
+    let mut docset_hierarchy = docset_root_dir.to_owned();
     docset_hierarchy.push("Contents");
     docset_hierarchy.push("Resources");
     create_dir_all(&docset_hierarchy).context(IoWriteSnafu)?;
-    generate_sqlite_index(&docset_root_dir, entries)?;
-
-    // Step 4: Copy the rustdoc to the docset directory
     docset_hierarchy.push("Documents");
-    copy_dir_recursive(&rustdoc_root_dir, &docset_hierarchy)?;
+    Ok(docset_hierarchy)
+}
+
+/// Write the index and the metadata of a docset bundle whose documentation was copied.
+fn finish_docset(
+    cfg: &DocsetParams,
+    cargo_metadata: &Metadata,
+    bundle: DocsetBundle,
+    docset_root_dir: &Path,
+    entries: Vec<DocsetEntry>,
+    entry_targets: &EntryTargets
+) -> Result<()> {
+    // Step 4: generate the SQLite database
+    generate_sqlite_index(
+        docset_root_dir,
+        entries,
+        cfg.entry_names,
+        &get_crate_versions(cargo_metadata),
+        entry_targets
+    )?;
 
     // Step 5: add the required metadata
-    if platform_family.is_none() {
+    if bundle.platform_family.is_none() {
         warn("no platform family was provided and none could be generated, consider adding the '--platform-family' option.");
     }
 
     write_metadata(
-        &docset_root_dir,
-        &docset_name,
-        get_docset_index(&cfg, &cargo_metadata),
-        platform_family
+        docset_root_dir,
+        &bundle.name,
+        bundle.index,
+        bundle.identifier,
+        bundle.platform_family
     )?;
 
-    println!(
-        "Docset successfully generated in {}",
-        docset_root_dir.to_string_lossy()
-    );
+    if cfg.tarix {
+        tarix::pack_documents(&docset_root_dir.join("Contents").join("Resources"))?;
+    }
+
+    // Step 6: package the docset, feeds and contributions point to its archive
+    let package = bundle.package.as_ref().and_then(|id| cargo_metadata.packages.iter().find(|p| &p.id == id));
+    if cfg.archive || cfg.archive_only || cfg.feed_url.is_some() || cfg.contribution_dir.is_some() {
+        let archive_path = archive::write_archive(docset_root_dir)?;
+        // The docset directory is only a temporary tree the archive is built from.
+        if cfg.archive_only {
+            remove_dir_all(docset_root_dir).context(IoWriteSnafu)?;
+        }
+        println!(
+            "Docset archive successfully generated in {}",
+            archive_path.to_string_lossy()
+        );
+        let version = package.map(|p| p.version.to_string());
+        match (&cfg.feed_url, &version) {
+            (Some(feed_url), Some(version)) => feed::write_feeds(&archive_path, &bundle.name, version, feed_url)?,
+            (Some(_), None) => warn("no version could be determined for the docset, its feeds were not generated."),
+            _ => ()
+        }
+        match (&cfg.contribution_dir, package.zip(version.as_deref())) {
+            (Some(contribution_dir), Some((package, version))) => {
+                let contribution_dir = contribution_dir.join(&bundle.file_stem);
+                let contribution = get_contribution(cfg, package, &bundle.name, version);
+                contribution::write_contribution(&contribution_dir, &archive_path, contribution)?;
+                println!(
+                    "Docset contribution successfully generated in {}",
+                    contribution_dir.to_string_lossy()
+                );
+            }
+            (Some(_), None) => warn("no package could be determined for the docset, its contribution was not generated."),
+            _ => ()
+        }
+    }
+    if !cfg.archive_only {
+        println!(
+            "Docset successfully generated in {}",
+            docset_root_dir.to_string_lossy()
+        );
+    }
+
+    Ok(())
+}
+
+/// Generate a docset from the documentation built for the given target triples: for the host if
+/// there is none, and merging the documentation of all of them if there are several.
+fn generate_target_docset(
+    cfg: &DocsetParams,
+    cargo_metadata: &Metadata,
+    documented: &DocumentedCrates,
+    external: &ExternalCrates,
+    target_dir: &Path,
+    targets: &[String]
+) -> Result<()> {
+    if cfg.split != Split::None {
+        let target = targets.first().map(String::as_str);
+        return generate_package_docsets(cfg, cargo_metadata, documented, external, target_dir, target);
+    }
+
+    let platform_family = get_docset_platform_family(cfg, cargo_metadata);
+    let mut bundle = DocsetBundle {
+        name: get_docset_name(cfg, cargo_metadata),
+        file_stem: platform_family.clone().unwrap_or_else(|| get_workspace_name(cargo_metadata)),
+        index: get_docset_index(cfg, cargo_metadata),
+        identifier: platform_family.clone(),
+        platform_family,
+        package: config::get_config_package(cfg, cargo_metadata).map(|p| p.id.clone())
+    };
+    if let [target] = targets {
+        bundle = bundle.for_target(target);
+    }
+    let docset_root_dir = bundle.root_dir(target_dir);
+
+    // Step 3: copy the rustdoc to the docset directory
+    // At this point, we need to start writing into the output docset directory, so create the
+    // hirerarchy, and clean it first if it already exists.
+    let documents_dir = create_docset_dir(&docset_root_dir)?;
+    let (entries, entry_targets) = if targets.len() > 1 {
+        let target_docs = targets
+            .iter()
+            .map(|target| {
+                Ok(merge::TargetDocs {
+                    target: target.clone(),
+                    rustdoc_root_dir: get_rustdoc_root_dir(target_dir, Some(target)),
+                    entries: get_entries(cfg, documented, target_dir, Some(target))?
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        merge::merge_target_docs(&target_docs, documented, external, &documents_dir)?
+    } else {
+        let target = targets.first().map(String::as_str);
+        let rustdoc_root_dir = get_rustdoc_root_dir(target_dir, target);
+        copy_dir_recursive(
+            &rustdoc_root_dir,
+            &documents_dir,
+            &|path| documented.is_stale(&rustdoc_root_dir, path),
+            &|name| !documented.is_stale(&rustdoc_root_dir, Path::new(name)),
+            &|path, html| external.rewrite_links(html, path)
+        )?;
+        (get_entries(cfg, documented, target_dir, target)?, HashMap::new())
+    };
+
+    finish_docset(cfg, cargo_metadata, bundle, &docset_root_dir, entries, &entry_targets)
+}
+
+/// Return the crate directories of a package which were documented in a rustdoc directory, its
+/// library first.
+fn get_package_crate_dirs(package: &Package, documented: &DocumentedCrates, rustdoc_root_dir: &Path) -> Vec<String> {
+    let mut targets = package.targets.iter().collect::<Vec<_>>();
+    targets.sort_by_key(|target| !external::is_lib_target(target));
+    let mut crate_dirs = Vec::new();
+    for target in targets {
+        let crate_dir = target.name.replace('-', "_");
+        if !crate_dirs.contains(&crate_dir)
+            && rustdoc_root_dir.join(&crate_dir).join("index.html").is_file()
+            && !documented.is_stale(rustdoc_root_dir, Path::new(&crate_dir))
+        {
+            crate_dirs.push(crate_dir);
+        }
+    }
+    crate_dirs
+}
+
+/// Return whether a docset entry belongs to one of the given crate directories.
+fn is_crate_entry(entry: &DocsetEntry, crate_dirs: &[String]) -> bool {
+    let crate_dir = entry.path.iter().next();
+    crate_dir.is_some_and(|crate_dir| crate_dirs.iter().any(|dir| crate_dir == dir.as_str()))
+}
 
+/// Generate one docset per documented package from the documentation built for the given target
+/// triple. Each docset only contains the documentation of the crates of its package.
+fn generate_package_docsets(
+    cfg: &DocsetParams,
+    cargo_metadata: &Metadata,
+    documented: &DocumentedCrates,
+    external: &ExternalCrates,
+    target_dir: &Path,
+    target: Option<&str>
+) -> Result<()> {
+    let rustdoc_root_dir = get_rustdoc_root_dir(target_dir, target);
+    let entries = get_entries(cfg, documented, target_dir, target)?;
+
+    let (mut packages, _excluded) = cfg.workspace.partition_packages(cargo_metadata);
+    if cfg.split == Split::Packages {
+        packages.extend(cargo_metadata.packages.iter().filter(|p| !cargo_metadata.workspace_members.contains(&p.id)));
+    }
+    // Several versions of a dependency share the same crate directory, so a single docset is
+    // generated for them.
+    let mut seen_crate_dirs = HashSet::new();
+    for package in packages {
+        let crate_dirs = get_package_crate_dirs(package, documented, &rustdoc_root_dir);
+        if crate_dirs.is_empty() || crate_dirs.iter().any(|dir| seen_crate_dirs.contains(dir)) {
+            continue;
+        }
+        seen_crate_dirs.extend(crate_dirs.iter().cloned());
+
+        let mut bundle = DocsetBundle {
+            name: package.name.clone(),
+            file_stem: package.name.clone(),
+            index: crate_dirs.first().cloned(),
+            identifier: Some(package.name.clone()),
+            platform_family: Some(package.name.clone()),
+            package: Some(package.id.clone())
+        };
+        if let Some(target) = target {
+            bundle = bundle.for_target(target);
+        }
+        let docset_root_dir = bundle.root_dir(target_dir);
+
+        let documents_dir = create_docset_dir(&docset_root_dir)?;
+        copy_dir_recursive(
+            &rustdoc_root_dir,
+            &documents_dir,
+            &|path| {
+                cargo_doc::crate_dir(&rustdoc_root_dir, path)
+                    .is_some_and(|dir| !crate_dirs.iter().any(|crate_dir| dir == crate_dir.as_str()))
+            },
+            &|name| crate_dirs.iter().any(|crate_dir| crate_dir == name),
+            &|path, html| external.rewrite_links(html, path)
+        )?;
+        let package_entries = entries
+            .iter()
+            .filter(|entry| is_crate_entry(entry, &crate_dirs))
+            .cloned()
+            .collect();
+
+        finish_docset(cfg, cargo_metadata, bundle, &docset_root_dir, package_entries, &HashMap::new())?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        fs::{copy, create_dir_all, read_dir, read_to_string},
+        path::{Path, PathBuf}
+    };
+
+    use cargo_metadata::Metadata;
+    use rusqlite::Connection;
+
+    use super::{
+        absolute_target_dir, clean_crate_docs, generate_sqlite_index, get_docset_index, get_external_crates,
+        get_rustdoc_root_dir, get_selected_bin, is_crate_entry, walk_rustdoc_dir, DocsetBundle, DocsetEntry, EntryType
+    };
+    use crate::{DocsetParams, EntryNames, Reexports};
+
+    pub(super) fn fixture_rustdoc_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/doc")
+    }
+
+    /// Read a file of the fixture rustdoc directory, given by its path relative to it.
+    pub(super) fn read_fixture(path: &str) -> String {
+        read_to_string(fixture_rustdoc_dir().join(path)).unwrap()
+    }
+
+    /// Return the `cargo metadata` output of the fixture workspace. Its members are `app`, whose
+    /// only target is the `my-app` binary, `a` and `b`.
+    pub(crate) fn fixture_metadata() -> Metadata {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/metadata/workspace.json");
+        serde_json::from_str(&read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn copy_dir(src: &Path, dst: &Path) {
+        create_dir_all(dst).unwrap();
+        for entry in read_dir(src).unwrap() {
+            let path = entry.unwrap().path();
+            let dst = dst.join(path.file_name().unwrap());
+            if path.is_dir() {
+                copy_dir(&path, &dst);
+            } else {
+                copy(&path, &dst).unwrap();
+            }
+        }
+    }
+
+    /// Copy the fixture rustdoc directory to `dst`.
+    pub(super) fn copy_fixture(dst: &Path) {
+        copy_dir(&fixture_rustdoc_dir(), dst);
+    }
+
+    /// Return the paths of the files of a directory relative to it, sorted.
+    pub(super) fn relative_files(dir: &Path) -> Vec<PathBuf> {
+        fn collect(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+            for entry in read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    collect(root, &path, files);
+                } else {
+                    files.push(path.strip_prefix(root).unwrap().to_owned());
+                }
+            }
+        }
+
+        let mut files = vec![];
+        collect(dir, dir, &mut files);
+        files.sort();
+        files
+    }
+
+    fn walk_fixture_with(reexports: Reexports) -> Vec<DocsetEntry> {
+        walk_rustdoc_dir(&fixture_rustdoc_dir(), reexports).expect("Could not walk the fixture rustdoc directory")
+    }
+
+    fn walk_fixture() -> Vec<DocsetEntry> {
+        walk_fixture_with(Reexports::Canonical)
+    }
+
+    fn find_entry<'a>(entries: &'a [DocsetEntry], name: &str) -> Option<&'a DocsetEntry> {
+        entries.iter().find(|e| e.name == name)
+    }
+
+    fn page_entries(entries: Vec<DocsetEntry>) -> Vec<DocsetEntry> {
+        entries.into_iter().filter(|e| e.fragment.is_none()).collect()
+    }
+
+    #[test]
+    fn test_recursive_walk_indexes_every_item_kind() {
+        let entries = page_entries(walk_fixture());
+
+        let expected = &[
+            ("fixture", EntryType::Package, "fixture/index.html"),
+            ("fixture::inner", EntryType::Module, "fixture/inner/index.html"),
+            ("fixture::my_attribute", EntryType::Attribute, "fixture/attr.my_attribute.html"),
+            ("fixture::MY_CONST", EntryType::Constant, "fixture/constant.MY_CONST.html"),
+            ("fixture::MyDerive", EntryType::Derive, "fixture/derive.MyDerive.html"),
+            ("fixture::MyEnum", EntryType::Enum, "fixture/enum.MyEnum.html"),
+            ("fixture::MyForeignType", EntryType::ForeignType, "fixture/foreigntype.MyForeignType.html"),
+            ("fixture::my_function", EntryType::Function, "fixture/fn.my_function.html"),
+            ("fixture::my_macro", EntryType::Macro, "fixture/macro.my_macro.html"),
+            ("fixture::MY_STATIC", EntryType::Static, "fixture/static.MY_STATIC.html"),
+            ("fixture::MyStruct", EntryType::Struct, "fixture/struct.MyStruct.html"),
+            ("fixture::MyTrait", EntryType::Trait, "fixture/trait.MyTrait.html"),
+            ("fixture::MyTraitAlias", EntryType::TraitAlias, "fixture/traitalias.MyTraitAlias.html"),
+            ("fixture::MyType", EntryType::Type, "fixture/type.MyType.html"),
+            ("fixture::MyUnion", EntryType::Union, "fixture/union.MyUnion.html"),
+            ("fixture::Hidden", EntryType::Struct, "fixture/struct.Hidden.html"),
+            ("fixture::inner::Inner", EntryType::Struct, "fixture/inner/struct.Inner.html"),
+            ("std", EntryType::Package, "std/index.html"),
+            ("std::u8", EntryType::Primitive, "std/primitive.u8.html"),
+            ("std::match", EntryType::Keyword, "std/keyword.match.html")
+        ];
+
+        for (name, ty, path) in expected {
+            let entry = find_entry(&entries, name);
+            assert!(entry.is_some(), "Expected entry {} in {:?}", name, entries);
+            let entry = entry.unwrap();
+            assert_eq!(&entry.ty, ty);
+            assert_eq!(entry.path, Path::new(path));
+        }
+        assert_eq!(entries.len(), expected.len(), "Unexpected entries in {:?}", entries);
+    }
+
+    #[test]
+    fn test_recursive_walk_skips_redirections() {
+        // The redirection from the private definition path of a re-exported item is not indexed.
+        let entries = walk_fixture_with(Reexports::Both);
+        assert!(find_entry(&entries, "fixture::private::Hidden").is_none());
+        assert!(!entries.iter().any(|e| e.path.starts_with("fixture/private")));
+    }
+
+    #[test]
+    fn test_recursive_walk_reexports() {
+        let reexports = [
+            DocsetEntry::new(
+                "fixture::Inner".to_owned(),
+                EntryType::Struct,
+                PathBuf::from("fixture/inner/struct.Inner.html"),
+                None
+            ),
+            DocsetEntry::new(
+                "fixture::Renamed".to_owned(),
+                EntryType::Struct,
+                PathBuf::from("fixture/inner/struct.Inner.html"),
+                None
+            )
+        ];
+        let canonical_name = "fixture::inner::Inner";
+
+        let entries = walk_fixture();
+        assert!(find_entry(&entries, "fixture::Inner").is_none());
+
+        let entries = walk_fixture_with(Reexports::Both);
+        for reexport in &reexports {
+            assert!(entries.contains(reexport), "Expected entry {:?} in {:?}", reexport, entries);
+        }
+        assert!(find_entry(&entries, canonical_name).is_some());
+        // Re-exports of items which are not documented are not indexed.
+        assert!(find_entry(&entries, "fixture::Debug").is_none());
+
+        let entries = walk_fixture_with(Reexports::Reexport);
+        for reexport in &reexports {
+            assert!(entries.contains(reexport), "Expected entry {:?} in {:?}", reexport, entries);
+        }
+        assert!(find_entry(&entries, canonical_name).is_none());
+        // Pages that are not re-exported are still indexed.
+        assert!(find_entry(&entries, "fixture::MyEnum").is_some());
+    }
+
+    #[test]
+    fn test_alias_entry() {
+        let entry = DocsetEntry::new(
+            "a::Foo::len".to_owned(),
+            EntryType::Method,
+            PathBuf::from("a/struct.Foo.html"),
+            Some("method.len".to_owned())
+        );
+        let alias = super::alias_entry("size", &entry);
+        assert_eq!(alias.name, "size (→ a::Foo::len)");
+        assert_eq!(alias.ty, EntryType::Method);
+        assert_eq!(alias.index_path(), "a/struct.Foo.html#method.len");
+    }
+
+    #[test]
+    fn test_recursive_walk_indexes_members() {
+        let entries = walk_fixture();
+
+        let expected = &[
+            ("fixture::MyStruct::field", EntryType::Field, "fixture/struct.MyStruct.html#structfield.field"),
+            ("fixture::MyStruct::new", EntryType::Method, "fixture/struct.MyStruct.html#method.new"),
+            ("fixture::MyStruct::ZERO", EntryType::Constant, "fixture/struct.MyStruct.html#associatedconstant.ZERO"),
+            ("fixture::MyStruct::Output", EntryType::Type, "fixture/struct.MyStruct.html#associatedtype.Output"),
+            ("fixture::MyStruct::required", EntryType::Method, "fixture/struct.MyStruct.html#method.required"),
+            ("fixture::MyStruct::clone", EntryType::Method, "fixture/struct.MyStruct.html#method.clone"),
+            ("fixture::MyStruct::clone_from", EntryType::Method, "fixture/struct.MyStruct.html#method.clone_from"),
+            ("fixture::MyEnum::First", EntryType::Variant, "fixture/enum.MyEnum.html#variant.First"),
+            ("fixture::MyEnum::Second", EntryType::Variant, "fixture/enum.MyEnum.html#variant.Second"),
+            ("fixture::MyTrait::Output", EntryType::Type, "fixture/trait.MyTrait.html#associatedtype.Output"),
+            ("fixture::MyTrait::required", EntryType::Method, "fixture/trait.MyTrait.html#tymethod.required"),
+            ("fixture::MyTrait::provided", EntryType::Method, "fixture/trait.MyTrait.html#method.provided"),
+            ("fixture::MyUnion::int", EntryType::Field, "fixture/union.MyUnion.html#structfield.int"),
+            ("fixture::MyUnion::float", EntryType::Field, "fixture/union.MyUnion.html#structfield.float"),
+            ("std::u8::MAX", EntryType::Constant, "std/primitive.u8.html#associatedconstant.MAX"),
+            ("std::u8::is_ascii", EntryType::Method, "std/primitive.u8.html#method.is_ascii"),
+            ("std::u8::add", EntryType::Method, "std/primitive.u8.html#method.add"),
+            ("std::u8::add", EntryType::Method, "std/primitive.u8.html#method.add-1")
+        ];
+
+        let members = entries
+            .iter()
+            .filter(|e| {
+                e.fragment.is_some() && !matches!(e.ty, EntryType::Impl | EntryType::Implementor | EntryType::Section)
+            })
+            .collect::<Vec<_>>();
+        for (name, ty, path) in expected {
+            let entry = members.iter().find(|e| &e.name == name && &e.index_path() == path);
+            assert!(entry.is_some(), "Expected entry {} ({}) in {:?}", name, path, members);
+            assert_eq!(&entry.unwrap().ty, ty);
+        }
+        assert_eq!(members.len(), expected.len(), "Unexpected entries in {:?}", members);
+    }
+
+    #[test]
+    fn test_recursive_walk_indexes_trait_implementations() {
+        let entries = walk_fixture();
+
+        let expected = &[
+            ("impl MyTrait for fixture::MyStruct", EntryType::Impl, "fixture/struct.MyStruct.html#impl-MyTrait-for-MyStruct"),
+            ("impl Clone for fixture::MyStruct", EntryType::Impl, "fixture/struct.MyStruct.html#impl-Clone-for-MyStruct"),
+            ("impl Add<u8> for u8", EntryType::Impl, "std/primitive.u8.html#impl-Add%3Cu8%3E-for-u8"),
+            ("impl Add<&u8> for u8", EntryType::Impl, "std/primitive.u8.html#impl-Add%3C%26u8%3E-for-u8"),
+            (
+                "impl MyTrait for fixture::MyStruct",
+                EntryType::Implementor,
+                "fixture/trait.MyTrait.html#impl-MyTrait-for-MyStruct"
+            ),
+            ("impl MyTrait for other::Other", EntryType::Implementor, "fixture/trait.MyTrait.html#implementors")
+        ];
+
+        let impls = entries
+            .iter()
+            .filter(|e| matches!(e.ty, EntryType::Impl | EntryType::Implementor))
+            .collect::<Vec<_>>();
+        for (name, ty, path) in expected {
+            let entry = impls.iter().find(|e| &e.name == name && &e.index_path() == path);
+            assert!(entry.is_some(), "Expected entry {} ({}) in {:?}", name, path, impls);
+            assert_eq!(&entry.unwrap().ty, ty);
+        }
+        // Inherent, auto trait and blanket implementations are not indexed.
+        assert_eq!(impls.len(), expected.len(), "Unexpected entries in {:?}", impls);
+    }
+
+    #[test]
+    fn test_recursive_walk_indexes_docblock_sections() {
+        let entries = walk_fixture();
+
+        let expected = &[
+            ("fixture - Getting started", "fixture/index.html#getting-started"),
+            ("fixture - Feature flags", "fixture/index.html#feature-flags"),
+            ("fixture::inner - Examples", "fixture/inner/index.html#examples")
+        ];
+
+        let sections = entries.iter().filter(|e| e.ty == EntryType::Section).collect::<Vec<_>>();
+        for (name, path) in expected {
+            let entry = sections.iter().find(|e| &e.name == name && &e.index_path() == path);
+            assert!(entry.is_some(), "Expected entry {} ({}) in {:?}", name, path, sections);
+        }
+        // The item list headings ("Modules", "Structs", ...) are not indexed.
+        assert_eq!(sections.len(), expected.len(), "Unexpected entries in {:?}", sections);
+    }
+
+    #[test]
+    fn test_recursive_walk_skips_blanket_implementation_members() {
+        let entries = walk_fixture();
+        assert!(find_entry(&entries, "fixture::MyStruct::into").is_none());
+    }
+
+    #[test]
+    fn test_generate_sqlite_index_writes_fragments() {
+        let docset_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(docset_dir.path().join("Contents/Resources")).unwrap();
+        let entries = vec![
+            DocsetEntry::new("a::Foo".to_owned(), EntryType::Struct, PathBuf::from("a/struct.Foo.html"), None),
+            DocsetEntry::new(
+                "a::Foo::bar".to_owned(),
+                EntryType::Method,
+                PathBuf::from("a/struct.Foo.html"),
+                Some("method.bar".to_owned())
+            )
+        ];
+        generate_sqlite_index(docset_dir.path(), entries, EntryNames::Qualified, &HashMap::new(), &HashMap::new()).unwrap();
+
+        let conn = Connection::open(docset_dir.path().join("Contents/Resources/docSet.dsidx")).unwrap();
+        let mut stmt = conn.prepare("SELECT name, type, path FROM searchIndex ORDER BY name").unwrap();
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(rows, vec![
+            ("a::Foo".to_owned(), "Struct".to_owned(), "a/struct.Foo.html".to_owned()),
+            ("a::Foo::bar".to_owned(), "Method".to_owned(), "a/struct.Foo.html#method.bar".to_owned())
+        ]);
+    }
+
+    #[test]
+    fn test_short_index_names() {
+        let crate_versions = HashMap::from([("a".to_owned(), "1.2.3".to_owned())]);
+        let index_name_and_path = |name: &str, ty, fragment: Option<&str>| {
+            DocsetEntry::new(name.to_owned(), ty, PathBuf::from("a/struct.Foo.html"), fragment.map(ToOwned::to_owned))
+                .index_name_and_path(EntryNames::Short, &crate_versions, &[])
+        };
+
+        assert_eq!(index_name_and_path("a::Foo::len", EntryType::Method, Some("method.len")), (
+            "len".to_owned(),
+            "<dash_entry_name=len><dash_entry_menuDescription=a::Foo%201.2.3>a/struct.Foo.html#method.len".to_owned()
+        ));
+        assert_eq!(index_name_and_path("a - Getting started", EntryType::Section, Some("getting-started")), (
+            "Getting started".to_owned(),
+            "<dash_entry_name=Getting%20started><dash_entry_menuDescription=a%201.2.3>a/struct.Foo.html#getting-started"
+                .to_owned()
+        ));
+        assert_eq!(index_name_and_path("impl Add<u8> for a::Foo", EntryType::Impl, Some("impl-Add")), (
+            "impl Add<u8> for a::Foo".to_owned(),
+            "<dash_entry_name=impl%20Add%3Cu8%3E%20for%20a::Foo><dash_entry_menuDescription=a%201.2.3>a/struct.Foo.html#impl-Add"
+                .to_owned()
+        ));
+        assert_eq!(index_name_and_path("a::Foo", EntryType::Struct, None), (
+            "Foo".to_owned(),
+            "<dash_entry_name=Foo><dash_entry_menuDescription=a%201.2.3>a/struct.Foo.html".to_owned()
+        ));
+        assert_eq!(
+            DocsetEntry::new("a::Foo".to_owned(), EntryType::Struct, PathBuf::from("a/struct.Foo.html"), None)
+                .index_name_and_path(EntryNames::Qualified, &crate_versions, &[]),
+            ("a::Foo".to_owned(), "a/struct.Foo.html".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_index_paths_label_targets() {
+        let entry = DocsetEntry::new("a::Foo".to_owned(), EntryType::Struct, PathBuf::from("a/struct.Foo.html"), None);
+        let targets = &["x86_64-pc-windows-gnu".to_owned(), "wasm32-unknown-unknown".to_owned()];
+        assert_eq!(entry.index_name_and_path(EntryNames::Qualified, &HashMap::new(), targets), (
+            "a::Foo".to_owned(),
+            "<dash_entry_name=a::Foo><dash_entry_menuDescription=%5Bx86_64-pc-windows-gnu%2C%20wasm32-unknown-unknown%5D>a/struct.Foo.html"
+                .to_owned()
+        ));
+    }
+
+    #[test]
+    fn test_get_rustdoc_root_dir() {
+        let target_dir = Path::new("/project/target");
+        assert_eq!(get_rustdoc_root_dir(target_dir, None), Path::new("/project/target/doc"));
+        assert_eq!(
+            get_rustdoc_root_dir(target_dir, Some("x86_64-pc-windows-gnu")),
+            Path::new("/project/target/x86_64-pc-windows-gnu/doc")
+        );
+    }
+
+    #[test]
+    fn test_absolute_target_dir() {
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(absolute_target_dir(Path::new("target-rel")).unwrap(), cwd.join("target-rel"));
+        assert_eq!(absolute_target_dir(Path::new("/project/target")).unwrap(), Path::new("/project/target"));
+        // The rustdoc directory of a relative target directory matches the one reported by cargo.
+        let target_dir = absolute_target_dir(Path::new("target-rel")).unwrap();
+        assert_eq!(get_rustdoc_root_dir(&target_dir, None), cwd.join("target-rel/doc"));
+    }
+
+    #[test]
+    fn test_clean_crate_docs() {
+        let target_dir = tempfile::tempdir().unwrap();
+        let rustdoc_root_dir = target_dir.path().join("doc");
+        let json_root_dir = target_dir.path().join("docset-rustdoc-json/doc");
+        for dir in ["mine", "src/mine", "dep", "src/dep"] {
+            std::fs::create_dir_all(rustdoc_root_dir.join(dir)).unwrap();
+        }
+        std::fs::create_dir_all(&json_root_dir).unwrap();
+        std::fs::write(json_root_dir.join("mine.json"), "{}").unwrap();
+
+        clean_crate_docs(&rustdoc_root_dir, &json_root_dir, &HashSet::from(["mine".to_owned()])).unwrap();
+
+        assert!(!rustdoc_root_dir.join("mine").exists());
+        assert!(!rustdoc_root_dir.join("src/mine").exists());
+        assert!(!json_root_dir.join("mine.json").exists());
+        // The documentation of the other crates is reused.
+        assert!(rustdoc_root_dir.join("dep").is_dir());
+        assert!(rustdoc_root_dir.join("src/dep").is_dir());
+    }
+
+    #[test]
+    fn test_get_external_crates() {
+        let metadata = fixture_metadata();
+        let external_crate_dirs = |cfg: DocsetParams| {
+            let mut crate_dirs = get_external_crates(&cfg, &metadata).crate_dirs().map(ToOwned::to_owned).collect::<Vec<_>>();
+            crate_dirs.sort_unstable();
+            crate_dirs
+        };
+        let mut cfg = DocsetParams { deps_depth: Some(1), ..Default::default() };
+        cfg.workspace.package = vec!["a".to_owned()];
+        // `git-dep` is not published on crates.io, so it is kept, and the other members are not
+        // dependencies of `a`.
+        assert_eq!(external_crate_dirs(cfg.clone()), ["deep"]);
+        cfg.deps_depth = Some(0);
+        assert_eq!(external_crate_dirs(cfg.clone()), ["deep", "dep1", "shared"]);
+
+        // The development and build dependencies are not documented, so they are not left out.
+        let mut cfg = DocsetParams { exclude_deps: vec!["*-dep".to_owned(), "dep?".to_owned()], ..Default::default() };
+        cfg.workspace.package = vec!["a".to_owned()];
+        assert_eq!(external_crate_dirs(cfg), ["dep1"]);
+    }
+
+    #[test]
+    fn test_get_selected_bin() {
+        let bin = |bin: &[&str], example: &[&str]| DocsetParams {
+            bin: bin.iter().map(|b| b.to_string()).collect(),
+            example: example.iter().map(|e| e.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(get_selected_bin(&bin(&["my-bin"], &[])), Some("my-bin"));
+        assert_eq!(get_selected_bin(&bin(&[], &["my-example"])), Some("my-example"));
+        assert_eq!(get_selected_bin(&bin(&["a", "b"], &[])), None);
+        assert_eq!(get_selected_bin(&bin(&["a"], &["b"])), None);
+        assert_eq!(get_selected_bin(&DocsetParams { lib: true, ..bin(&["a"], &[]) }), None);
+    }
+
+    #[test]
+    fn test_get_docset_index() {
+        let metadata = fixture_metadata();
+        let index = |packages: &[&str], bin: &[&str]| {
+            let mut cfg = DocsetParams { bin: bin.iter().map(|b| b.to_string()).collect(), ..Default::default() };
+            cfg.workspace.package = packages.iter().map(|p| p.to_string()).collect();
+            get_docset_index(&cfg, &metadata)
+        };
+        // The documentation of a binary is named after the binary rather than its package.
+        assert_eq!(index(&["app"], &[]).as_deref(), Some("my_app"));
+        assert_eq!(index(&["app"], &["my-app"]).as_deref(), Some("my_app"));
+        assert_eq!(index(&["a"], &[]).as_deref(), Some("a"));
+        // The fixture workspace is virtual, so there is no index for all its members.
+        assert_eq!(index(&[], &[]), None);
+    }
+
+    #[test]
+    fn test_docset_bundle_for_target() {
+        let bundle = DocsetBundle {
+            name: "my-crate".to_owned(),
+            file_stem: "my-crate".to_owned(),
+            index: Some("my_crate".to_owned()),
+            identifier: Some("my-crate".to_owned()),
+            platform_family: Some("my-crate".to_owned()),
+            package: None
+        }
+        .for_target("wasm32-unknown-unknown");
+        assert_eq!(bundle.name, "my-crate (wasm32-unknown-unknown)");
+        assert_eq!(bundle.identifier.as_deref(), Some("my-crate-wasm32-unknown-unknown"));
+        assert_eq!(bundle.index.as_deref(), Some("my_crate"));
+        assert_eq!(
+            bundle.root_dir(Path::new("target")),
+            Path::new("target/docset/my-crate-wasm32-unknown-unknown.docset")
+        );
+    }
+
+    #[test]
+    fn test_is_crate_entry() {
+        let crate_dirs = ["fixture".to_owned(), "fixture_cli".to_owned()];
+        let entry = |path: &str| DocsetEntry::new("a".to_owned(), EntryType::Struct, PathBuf::from(path), None);
+        assert!(is_crate_entry(&entry("fixture/struct.MyStruct.html"), &crate_dirs));
+        assert!(is_crate_entry(&entry("fixture_cli/fn.main.html"), &crate_dirs));
+        assert!(!is_crate_entry(&entry("other/struct.Other.html"), &crate_dirs));
+        assert!(!is_crate_entry(&entry("fixture_other/index.html"), &crate_dirs));
+    }
+
+    #[test]
+    fn test_entry_type_dash_names() {
+        assert_eq!(EntryType::Attribute.to_string(), "Attribute");
+        assert_eq!(EntryType::Derive.to_string(), "Macro");
+        assert_eq!(EntryType::ForeignType.to_string(), "Type");
+        assert_eq!(EntryType::Impl.to_string(), "Instance");
+        assert_eq!(EntryType::Implementor.to_string(), "Protocol");
+        assert_eq!(EntryType::Keyword.to_string(), "Keyword");
+        assert_eq!(EntryType::Primitive.to_string(), "Type");
+        assert_eq!(EntryType::Static.to_string(), "Global");
+        assert_eq!(EntryType::TraitAlias.to_string(), "Trait");
+        assert_eq!(EntryType::Union.to_string(), "Union");
+    }
+}
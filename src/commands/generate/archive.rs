@@ -0,0 +1,144 @@
+//! Packaging of docsets as gzip-compressed tarballs, the format in which Dash and Zeal download
+//! docsets.
+//!
+//! The archives contain the `.docset` bundle directory at their root. They are deterministic: the
+//! entries are sorted, and their timestamps, owners and permissions are normalized, so that
+//! archiving the same docset twice gives the same bytes.
+
+use crate::error::*;
+
+use flate2::{Compression, GzBuilder};
+use snafu::ResultExt;
+use tar::{Builder, EntryType, Header};
+
+use std::{
+    fs::{read_dir, File},
+    io::{empty, BufWriter, Write},
+    path::{Path, PathBuf}
+};
+
+/// Return the path of the archive of a docset bundle, next to the bundle directory.
+pub fn archive_path(docset_root_dir: &Path) -> PathBuf {
+    docset_root_dir.with_extension("tgz")
+}
+
+/// Return a tar header with normalized metadata.
+fn header(entry_type: EntryType, mode: u32, size: u64) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(entry_type);
+    header.set_mode(mode);
+    header.set_size(size);
+    header.set_mtime(0);
+    header.set_uid(0);
+    header.set_gid(0);
+    header
+}
+
+/// Append `dir` to a tarball as `archive_dir`, followed by its contents in a sorted order.
+/// `on_file` is called before each file is appended, with its path in the tarball and its size.
+pub fn append_dir<W, F>(builder: &mut Builder<W>, dir: &Path, archive_dir: &Path, on_file: &mut F) -> Result<()>
+where
+    W: Write,
+    F: FnMut(&mut Builder<W>, &Path, u64) -> Result<()>
+{
+    let mut dir_header = header(EntryType::Directory, 0o755, 0);
+    builder.append_data(&mut dir_header, archive_dir, empty()).context(IoWriteSnafu)?;
+
+    let mut paths = read_dir(dir)
+        .context(IoReadSnafu)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .context(IoReadSnafu)?;
+    paths.sort();
+    for path in paths {
+        let archive_path = archive_dir.join(path.file_name().unwrap());
+        if path.is_dir() {
+            append_dir(builder, &path, &archive_path, on_file)?;
+        } else if path.is_file() {
+            let file = File::open(&path).context(IoReadSnafu)?;
+            let size = file.metadata().context(IoReadSnafu)?.len();
+            on_file(builder, &archive_path, size)?;
+            let mut header = header(EntryType::Regular, 0o644, size);
+            builder.append_data(&mut header, &archive_path, file).context(IoWriteSnafu)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the archive of a docset bundle next to it, and return its path.
+pub fn write_archive(docset_root_dir: &Path) -> Result<PathBuf> {
+    let archive_path = archive_path(docset_root_dir);
+    let file = File::create(&archive_path).context(IoWriteSnafu)?;
+    let encoder = GzBuilder::new().mtime(0).write(BufWriter::new(file), Compression::best());
+    let mut builder = Builder::new(encoder);
+
+    let bundle_dir = Path::new(docset_root_dir.file_name().unwrap());
+    append_dir(&mut builder, docset_root_dir, bundle_dir, &mut |_, _, _| Ok(()))?;
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .and_then(|mut writer| writer.flush())
+        .context(IoWriteSnafu)?;
+    Ok(archive_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{read, File},
+        path::Path
+    };
+
+    use flate2::read::GzDecoder;
+    use tar::Archive;
+
+    use super::write_archive;
+    use crate::commands::generate::{
+        tests::{copy_fixture, relative_files},
+        write_metadata
+    };
+
+    #[test]
+    fn test_write_archive_is_deterministic() {
+        let dir = tempfile::tempdir().unwrap();
+        let docset_root_dir = dir.path().join("fixture.docset");
+        copy_fixture(&docset_root_dir.join("Contents/Resources/Documents"));
+        write_metadata(&docset_root_dir, "Fixture", Some("fixture".to_owned()), None, None).unwrap();
+
+        let archive_path = write_archive(&docset_root_dir).unwrap();
+        assert_eq!(archive_path, dir.path().join("fixture.tgz"));
+        let first = read(&archive_path).unwrap();
+        let archive_path = write_archive(&docset_root_dir).unwrap();
+        assert_eq!(first, read(&archive_path).unwrap());
+
+        let mut archive = Archive::new(GzDecoder::new(File::open(&archive_path).unwrap()));
+        let paths = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                assert_eq!(entry.header().mtime().unwrap(), 0);
+                assert_eq!(entry.header().uid().unwrap(), 0);
+                entry.path().unwrap().into_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(paths[0], Path::new("fixture.docset"));
+        // Every file of the docset is archived once, in the order of its path.
+        let files = paths
+            .iter()
+            .filter(|path| dir.path().join(path).is_file())
+            .map(|path| path.strip_prefix("fixture.docset").unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(files, relative_files(&docset_root_dir));
+
+        let unpacked = tempfile::tempdir().unwrap();
+        Archive::new(GzDecoder::new(File::open(&archive_path).unwrap())).unpack(unpacked.path()).unwrap();
+        for file in relative_files(&docset_root_dir) {
+            assert_eq!(
+                read(unpacked.path().join("fixture.docset").join(&file)).unwrap(),
+                read(docset_root_dir.join(&file)).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Invocation of `cargo doc`, whose JSON messages tell which crates it documented.
+
+use crate::{cargo::CargoOptions, error::*, io::*};
+
+use super::external::ExternalCrates;
+
+use cargo_metadata::{diagnostic::DiagnosticLevel, Message};
+use snafu::ResultExt;
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::Stdio
+};
+
+/// The crates documented by a `cargo doc` invocation, keyed by the rustdoc directory they were
+/// documented in, which depends on the target.
+#[derive(Debug, Default)]
+pub struct DocumentedCrates(HashMap<PathBuf, HashSet<String>>);
+
+impl DocumentedCrates {
+    /// Whether a path of a rustdoc directory belongs to a crate which was not documented by this
+    /// invocation, i.e. a leftover from a previous one. Paths which do not belong to a crate, and
+    /// paths of rustdoc directories this invocation did not write to, are never stale.
+    pub fn is_stale(&self, rustdoc_root_dir: &Path, path: &Path) -> bool {
+        let crates = match self.0.get(rustdoc_root_dir) {
+            Some(crates) => crates,
+            None => return false
+        };
+        crate_dir(rustdoc_root_dir, path).is_some_and(|dir| !crates.contains(&*dir.to_string_lossy()))
+    }
+
+    /// Consider the crates left out of the docset as not documented, so that they are not
+    /// indexed nor copied.
+    pub fn exclude(&mut self, external: &ExternalCrates) {
+        for crates in self.0.values_mut() {
+            for crate_dir in external.crate_dirs() {
+                crates.remove(crate_dir);
+            }
+        }
+    }
+}
+
+/// Return the name of the crate directory a path of a rustdoc directory belongs to, if any.
+pub fn crate_dir<'p>(rustdoc_root_dir: &Path, path: &'p Path) -> Option<&'p OsStr> {
+    let mut components = path.iter();
+    match components.next() {
+        // Sources are stored in a directory per crate too.
+        Some(dir) if dir == "src" => components.next(),
+        Some(dir) if rustdoc_root_dir.join(dir).join("index.html").is_file() => Some(dir),
+        _ => None
+    }
+}
+
+/// Report a diagnostic emitted while documenting a crate.
+fn report_diagnostic(crate_name: &str, diagnostic: &cargo_metadata::diagnostic::Diagnostic) {
+    // Diagnostics without a location only summarize the other ones, e.g. "1 warning emitted".
+    let span = match diagnostic.spans.iter().find(|s| s.is_primary) {
+        Some(span) => span,
+        None => return
+    };
+    let msg = format!(
+        "{}: {} ({}:{}:{})",
+        crate_name, diagnostic.message, span.file_name, span.line_start, span.column_start
+    );
+    match diagnostic.level {
+        DiagnosticLevel::Error | DiagnosticLevel::Ice => error(&msg),
+        DiagnosticLevel::Warning => warn(&msg),
+        _ => ()
+    }
+}
+
+/// Parse the JSON messages of `cargo doc`, reporting the diagnostics and returning the documented
+/// crates.
+fn parse_messages<R: BufRead>(messages: R) -> Result<DocumentedCrates> {
+    let mut documented = DocumentedCrates::default();
+    for message in Message::parse_stream(messages) {
+        match message.context(IoReadSnafu)? {
+            Message::CompilerArtifact(artifact) => {
+                // Dependencies are only checked, documented crates are the ones with an index page.
+                for index in artifact.filenames.iter().filter(|f| f.file_name() == Some("index.html")) {
+                    let (crate_dir, rustdoc_root_dir) = match index.parent() {
+                        Some(crate_dir) => (crate_dir, crate_dir.parent()),
+                        None => continue
+                    };
+                    if let (Some(crate_name), Some(rustdoc_root_dir)) = (crate_dir.file_name(), rustdoc_root_dir) {
+                        documented
+                            .0
+                            .entry(rustdoc_root_dir.as_std_path().to_owned())
+                            .or_default()
+                            .insert(crate_name.to_owned());
+                    }
+                }
+            }
+            Message::CompilerMessage(msg) => report_diagnostic(&msg.target.name, &msg.message),
+            _ => ()
+        }
+    }
+    Ok(documented)
+}
+
+/// Run `cargo doc` with the given options and arguments, and return the crates it documented.
+pub fn run_cargo_doc(cargo: &CargoOptions, args: Vec<String>) -> Result<DocumentedCrates> {
+    let mut cargo_doc = cargo
+        .command("doc")
+        .args(args)
+        .arg("--message-format=json")
+        .stdout(Stdio::piped())
+        .spawn()
+        .context(SpawnSnafu)?;
+    let documented = parse_messages(BufReader::new(cargo_doc.stdout.take().unwrap()))?;
+    let cargo_doc_result = cargo_doc.wait().context(SpawnSnafu)?;
+    if !cargo_doc_result.success() {
+        return CargoDocSnafu {
+            code: cargo_doc_result.code()
+        }
+        .fail();
+    }
+    Ok(documented)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, path::Path};
+
+    use super::{crate_dir, parse_messages};
+    use crate::commands::generate::tests::fixture_rustdoc_dir;
+
+    const MESSAGES: &str = r#"{"reason":"compiler-artifact","package_id":"dep 0.1.0 (registry+https://github.com/rust-lang/crates.io-index)","manifest_path":"/dep/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"dep","src_path":"/dep/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/project/target/debug/deps/libdep-0123.rmeta"],"executable":null,"fresh":false}
+{"reason":"compiler-message","package_id":"fixture 0.1.0 (path+file:///project)","manifest_path":"/project/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"fixture","src_path":"/project/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true},"message":{"rendered":"warning: unresolved link to `Foo`\n","children":[],"level":"warning","message":"unresolved link to `Foo`","spans":[{"byte_end":10,"byte_start":5,"column_end":11,"column_start":6,"expansion":null,"file_name":"src/lib.rs","is_primary":true,"label":null,"line_end":1,"line_start":1,"suggested_replacement":null,"suggestion_applicability":null,"text":[]}],"code":null}}
+{"reason":"compiler-artifact","package_id":"fixture 0.1.0 (path+file:///project)","manifest_path":"/project/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"fixture","src_path":"/project/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/project/target/x86_64-pc-windows-gnu/doc/fixture/index.html"],"executable":null,"fresh":true}
+{"reason":"build-finished","success":true}
+"#;
+
+    #[test]
+    fn test_parse_messages() {
+        let documented = parse_messages(MESSAGES.as_bytes()).unwrap();
+        let root = Path::new("/project/target/x86_64-pc-windows-gnu/doc");
+        assert_eq!(documented.0.len(), 1, "Unexpected crates {:?}", documented);
+        assert!(documented.0[root].contains("fixture"));
+        assert!(!documented.0[root].contains("dep"));
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let root = fixture_rustdoc_dir();
+        let mut documented = super::DocumentedCrates::default();
+        documented.0.entry(root.clone()).or_default().insert("fixture".to_owned());
+
+        assert!(!documented.is_stale(&root, Path::new("fixture/struct.MyStruct.html")));
+        assert!(!documented.is_stale(&root, Path::new("src/fixture/lib.rs.html")));
+        assert!(!documented.is_stale(&root, Path::new("search-index.js")));
+        assert!(!documented.is_stale(&root, Path::new("implementors")));
+        assert!(documented.is_stale(&root, Path::new("std/primitive.u8.html")));
+        assert!(documented.is_stale(&root, Path::new("src/std/lib.rs.html")));
+        // Directories which were not written to by this invocation are left alone.
+        assert!(!documented.is_stale(Path::new("/elsewhere"), Path::new("std/primitive.u8.html")));
+    }
+
+    #[test]
+    fn test_crate_dir() {
+        let root = fixture_rustdoc_dir();
+        assert_eq!(crate_dir(&root, Path::new("fixture/inner/index.html")), Some(OsStr::new("fixture")));
+        assert_eq!(crate_dir(&root, Path::new("src/other/lib.rs.html")), Some(OsStr::new("other")));
+        assert_eq!(crate_dir(&root, Path::new("static.files/main.js")), None);
+    }
+}
@@ -0,0 +1,240 @@
+//! Layout of a docset submitted to the Dash user-contributed docsets repository, see
+//! <https://github.com/Kapeli/Dash-User-Contributions#contribute-a-new-docset>.
+//!
+//! A contribution directory contains the docset archive, its description in `docset.json`, its
+//! icons and a README. Previously published versions are kept under `versions/<version>/`.
+
+use crate::{error::*, io::*};
+
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use std::{
+    fs::{copy, create_dir_all, read_to_string, write},
+    path::Path
+};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Author {
+    pub name: String,
+    pub link: String
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct SpecificVersion {
+    version: String,
+    archive: String
+}
+
+/// The contents of `docset.json`. Unknown fields of an existing file are preserved.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DocsetJson {
+    name: String,
+    version: String,
+    archive: String,
+    author: Author,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    specific_versions: Vec<SpecificVersion>,
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>
+}
+
+/// The description of a contributed docset.
+pub struct Contribution<'a> {
+    pub name: &'a str,
+    pub description: Option<&'a str>,
+    pub version: &'a str,
+    pub author: Author,
+    pub aliases: &'a [String],
+    /// Directory in which the icons of the docset are looked up.
+    pub icon_dir: Option<&'a Path>
+}
+
+/// Return the path of an archive of the given version, relative to the contribution directory.
+fn version_archive(version: &str, archive: &str) -> String {
+    format!("versions/{}/{}", version, archive)
+}
+
+/// Write the contribution of a docset archive to `contribution_dir`, keeping the versions it
+/// already contains.
+pub fn write_contribution(contribution_dir: &Path, archive_path: &Path, contribution: Contribution) -> Result<()> {
+    let archive = archive_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let json_path = contribution_dir.join("docset.json");
+    let mut docset_json = if json_path.is_file() {
+        let json = read_to_string(&json_path).context(IoReadSnafu)?;
+        serde_json::from_str::<DocsetJson>(&json).context(ContributionSnafu { path: &json_path })?
+    } else {
+        DocsetJson::default()
+    };
+    create_dir_all(contribution_dir).context(IoWriteSnafu)?;
+
+    // Keep the archive of the previous version, if it was not already.
+    let previous_archive = contribution_dir.join(&docset_json.archive);
+    if !docset_json.version.is_empty() && docset_json.version != contribution.version && previous_archive.is_file() {
+        let version_archive = contribution_dir.join(version_archive(&docset_json.version, &docset_json.archive));
+        if !version_archive.is_file() {
+            create_dir_all(version_archive.parent().unwrap()).context(IoWriteSnafu)?;
+            copy(&previous_archive, &version_archive).context(IoWriteSnafu)?;
+        }
+        let previous_version = SpecificVersion {
+            version: docset_json.version.clone(),
+            archive: version_archive.strip_prefix(contribution_dir).unwrap().to_string_lossy().into_owned()
+        };
+        if !docset_json.specific_versions.contains(&previous_version) {
+            docset_json.specific_versions.push(previous_version);
+        }
+    }
+
+    // Store the new version both as the current one and among the specific ones.
+    let current_version = SpecificVersion {
+        version: contribution.version.to_owned(),
+        archive: version_archive(contribution.version, &archive)
+    };
+    create_dir_all(contribution_dir.join(&current_version.archive).parent().unwrap()).context(IoWriteSnafu)?;
+    copy(archive_path, contribution_dir.join(&current_version.archive)).context(IoWriteSnafu)?;
+    copy(archive_path, contribution_dir.join(&archive)).context(IoWriteSnafu)?;
+    docset_json.specific_versions.retain(|v| v.version != current_version.version);
+    docset_json.specific_versions.insert(0, current_version);
+
+    docset_json.name = contribution.name.to_owned();
+    docset_json.version = contribution.version.to_owned();
+    docset_json.archive = archive;
+    // Keep the author of an existing contribution when it can't be determined.
+    if !contribution.author.name.is_empty() {
+        docset_json.author.name = contribution.author.name;
+    }
+    if !contribution.author.link.is_empty() {
+        docset_json.author.link = contribution.author.link;
+    }
+    if !contribution.aliases.is_empty() {
+        docset_json.aliases = contribution.aliases.to_vec();
+    }
+    if docset_json.author.name.is_empty() {
+        warn(&format!(
+            "no author was found for the docset, use the '--contribution-author' option or add it to {} before submitting the docset.",
+            contribution_dir.to_string_lossy()
+        ));
+    }
+    let json = serde_json::to_string_pretty(&docset_json).expect("Could not serialize docset.json");
+    write(json_path, json + "\n").context(IoWriteSnafu)?;
+
+    for icon in ["icon.png", "icon@2x.png"] {
+        let src = contribution.icon_dir.map(|dir| dir.join(icon)).filter(|src| src.is_file());
+        match src {
+            Some(src) => {
+                copy(src, contribution_dir.join(icon)).context(IoWriteSnafu)?;
+            }
+            None if !contribution_dir.join(icon).is_file() => warn(&format!(
+                "no {} was found next to the package manifest, add it to {} before submitting the docset.",
+                icon,
+                contribution_dir.to_string_lossy()
+            )),
+            None => ()
+        }
+    }
+
+    // The README is meant to be edited by the maintainer, so it is only written once.
+    let readme_path = contribution_dir.join("README.md");
+    if !readme_path.is_file() {
+        let mut readme = format!("# {} Dash docset\n\n", contribution.name);
+        if let Some(description) = contribution.description {
+            readme.push_str(&format!("* Docset description: {}\n", description));
+        }
+        let author = &docset_json.author;
+        match (author.name.as_str(), author.link.as_str()) {
+            ("", _) => (),
+            (name, "") => readme.push_str(&format!("* Docset maintainer: {}\n", name)),
+            (name, link) => readme.push_str(&format!("* Docset maintainer: [{}]({})\n", name, link))
+        }
+        readme.push_str("* Generated with [cargo-docset](https://github.com/Robzz/cargo-docset).\n");
+        write(readme_path, readme).context(IoWriteSnafu)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{read, read_to_string, write},
+        path::{Path, PathBuf}
+    };
+
+    use super::{write_contribution, Author, Contribution};
+    use crate::commands::generate::{archive::write_archive, tests::copy_fixture};
+
+    fn contribution(version: &str) -> Contribution<'_> {
+        Contribution {
+            name: "Fixture",
+            description: Some("Fixture crate."),
+            version,
+            author: Author {
+                name: "Me".to_owned(),
+                link: "https://example.com/fixture".to_owned()
+            },
+            aliases: &[],
+            icon_dir: None
+        }
+    }
+
+    /// Archive the fixture rustdoc output as a docset, and return the archive path and contents.
+    fn fixture_archive(dir: &Path) -> (PathBuf, Vec<u8>) {
+        let docset_root_dir = dir.join("fixture.docset");
+        copy_fixture(&docset_root_dir.join("Contents/Resources/Documents"));
+        let archive_path = write_archive(&docset_root_dir).unwrap();
+        let contents = read(&archive_path).unwrap();
+        (archive_path, contents)
+    }
+
+    #[test]
+    fn test_write_contribution_keeps_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let contribution_dir = dir.path().join("contribution");
+
+        let (archive_path, first_archive) = fixture_archive(dir.path());
+        write_contribution(&contribution_dir, &archive_path, contribution("0.1.0")).unwrap();
+        // Unknown fields are preserved.
+        let json = read_to_string(contribution_dir.join("docset.json")).unwrap();
+        write(contribution_dir.join("docset.json"), json.replacen('{', "{\n  \"extra\": 1,", 1)).unwrap();
+        write(dir.path().join("fixture.docset/Contents/Info.plist"), "<plist/>").unwrap();
+        let (archive_path, second_archive) = fixture_archive(dir.path());
+        assert_ne!(first_archive, second_archive);
+        let mut new_contribution = contribution("0.2.0");
+        new_contribution.author.name.clear();
+        write_contribution(&contribution_dir, &archive_path, new_contribution).unwrap();
+
+        let read_file = |path: &str| read(contribution_dir.join(path)).unwrap();
+        assert_eq!(read_file("fixture.tgz"), second_archive);
+        assert_eq!(read_file("versions/0.2.0/fixture.tgz"), second_archive);
+        assert_eq!(read_file("versions/0.1.0/fixture.tgz"), first_archive);
+        let readme = read_to_string(contribution_dir.join("README.md")).unwrap();
+        assert!(readme.contains("* Docset maintainer: [Me](https://example.com/fixture)"));
+
+        let json = serde_json::from_slice::<serde_json::Value>(&read_file("docset.json")).unwrap();
+        assert_eq!(json["name"], "Fixture");
+        assert_eq!(json["version"], "0.2.0");
+        assert_eq!(json["archive"], "fixture.tgz");
+        assert_eq!(json["author"]["name"], "Me");
+        assert_eq!(json["extra"], 1);
+        assert_eq!(json["specific_versions"][0]["version"], "0.2.0");
+        assert_eq!(json["specific_versions"][0]["archive"], "versions/0.2.0/fixture.tgz");
+        assert_eq!(json["specific_versions"][1]["version"], "0.1.0");
+        assert_eq!(json["specific_versions"][1]["archive"], "versions/0.1.0/fixture.tgz");
+        assert_eq!(json["specific_versions"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_write_contribution_without_author() {
+        let dir = tempfile::tempdir().unwrap();
+        let contribution_dir = dir.path().join("contribution");
+        let (archive_path, _) = fixture_archive(dir.path());
+        let mut contribution = contribution("0.1.0");
+        contribution.author = Author::default();
+        write_contribution(&contribution_dir, &archive_path, contribution).unwrap();
+
+        // No empty maintainer is written to the README.
+        let readme = read_to_string(contribution_dir.join("README.md")).unwrap();
+        assert!(!readme.contains("Docset maintainer"), "Unexpected README {}", readme);
+    }
+}
@@ -0,0 +1,263 @@
+//! Crates whose documentation is left out of the docset.
+//!
+//! The documentation of these crates is built by `cargo doc` anyway, so the pages of the other
+//! crates link to it. These links are rewritten to point to the documentation published on
+//! docs.rs instead, which only hosts the packages published on crates.io.
+
+use super::html;
+
+use cargo_metadata::{DependencyKind, Metadata, Package, PackageId, Target};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path
+};
+
+/// The crates left out of the docset, keyed by crate directory, along with the docs.rs URL of the
+/// documentation of their package.
+#[derive(Debug, Default)]
+pub struct ExternalCrates(HashMap<String, String>);
+
+/// Whether a package target is a library, i.e. whether it is documented for dependencies.
+pub fn is_lib_target(target: &Target) -> bool {
+    target.kind.iter().any(|kind| kind.ends_with("lib") || kind == "proc-macro")
+}
+
+/// Whether a name matches a glob pattern, in which `*` matches any sequence of characters and `?`
+/// matches any single character.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    // Position in the pattern after the last `*`, and in the name where it started matching.
+    let mut backtrack = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p;
+                    n = star_n + 1;
+                }
+                None => return false
+            }
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Whether a package comes from crates.io, whose packages are documented on docs.rs.
+fn is_on_docs_rs(package: &Package) -> bool {
+    package
+        .source
+        .as_ref()
+        .is_some_and(|source| source.is_crates_io() || source.repr == "sparse+https://index.crates.io/")
+}
+
+impl ExternalCrates {
+    /// Leave the library of a package out of the docset. Return false and keep it in the docset if
+    /// its package is not published on crates.io, as docs.rs does not document it.
+    pub fn insert(&mut self, package: &Package) -> bool {
+        if !is_on_docs_rs(package) {
+            return false;
+        }
+        let url = format!("https://docs.rs/{}/{}/", package.name, package.version);
+        for target in package.targets.iter().filter(|target| is_lib_target(target)) {
+            self.0.insert(target.name.replace('-', "_"), url.clone());
+        }
+        true
+    }
+
+    /// Keep a crate in the docset, e.g. because another version of its package is kept.
+    pub fn remove(&mut self, crate_dir: &str) {
+        self.0.remove(crate_dir);
+    }
+
+    /// Return the crate directories of the external crates.
+    pub fn crate_dirs(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// Rewrite the relative links of a page pointing into the documentation of external crates to
+    /// docs.rs. `page_path` is the path of the page relative to the rustdoc directory.
+    pub fn rewrite_links(&self, html: &str, page_path: &Path) -> String {
+        if self.0.is_empty() {
+            return html.to_owned();
+        }
+        let mut rewritten = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(start) = rest.find("href=\"") {
+            let value_start = start + "href=\"".len();
+            let value_len = match rest[value_start..].find('"') {
+                Some(len) => len,
+                None => break
+            };
+            let href = &rest[value_start..value_start + value_len];
+            rewritten.push_str(&rest[..value_start]);
+            match self.external_url(href, page_path) {
+                Some(url) => rewritten.push_str(&url),
+                None => rewritten.push_str(href)
+            }
+            rest = &rest[value_start + value_len..];
+        }
+        rewritten.push_str(rest);
+        rewritten
+    }
+
+    /// Return the docs.rs URL a relative link of a page should point to, if it points into the
+    /// documentation of an external crate.
+    fn external_url(&self, href: &str, page_path: &Path) -> Option<String> {
+        let resolved = html::resolve_href(page_path, href)?;
+        let fragment = href.find(['#', '?']).map_or("", |i| &href[i..]);
+
+        let mut crate_components = resolved.iter();
+        let crate_dir = match crate_components.next()? {
+            // Sources are stored in a directory per crate too.
+            dir if dir == "src" => crate_components.next()?,
+            dir => dir
+        };
+        let url = self.0.get(&*crate_dir.to_string_lossy())?;
+        let path = resolved.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/");
+        Some(format!("{}{}{}", url, path, fragment))
+    }
+}
+
+/// Return the dependency level of the packages the given packages depend on, directly or not,
+/// i.e. the length of the shortest path to them in the dependency graph, or `None` if the
+/// dependency graph was not resolved. Only normal dependencies are considered, as these are the
+/// ones `cargo doc` documents.
+pub fn dependency_levels<'m>(metadata: &'m Metadata, packages: &[&'m Package]) -> Option<HashMap<&'m PackageId, usize>> {
+    let resolve = metadata.resolve.as_ref()?;
+    let nodes = resolve.nodes.iter().map(|node| (&node.id, node)).collect::<HashMap<_, _>>();
+
+    // The graph is walked breadth-first, so packages are reached through their shortest path first.
+    let mut levels = packages.iter().map(|p| (&p.id, 0)).collect::<HashMap<&PackageId, usize>>();
+    let mut queue = packages.iter().map(|p| &p.id).collect::<VecDeque<_>>();
+    while let Some(id) = queue.pop_front() {
+        let level = levels[id];
+        let deps = nodes.get(id).into_iter().flat_map(|node| &node.deps);
+        for dep in deps {
+            // Older versions of cargo do not report the dependency kinds.
+            let is_normal = dep.dep_kinds.is_empty()
+                || dep.dep_kinds.iter().any(|info| info.kind == DependencyKind::Normal);
+            if is_normal && !levels.contains_key(&dep.pkg) {
+                levels.insert(&dep.pkg, level + 1);
+                queue.push_back(&dep.pkg);
+            }
+        }
+    }
+    Some(levels)
+}
+
+/// Return the dependencies more than `depth` dependency levels away from the given packages in the
+/// dependency graph. Workspace members are never left out.
+pub fn deps_beyond_depth<'m>(metadata: &'m Metadata, packages: &[&'m Package], depth: usize) -> Vec<&'m Package> {
+    let levels = dependency_levels(metadata, packages).unwrap_or_default();
+    metadata
+        .packages
+        .iter()
+        .filter(|p| levels.get(&p.id).is_some_and(|level| *level > depth))
+        .filter(|p| !metadata.workspace_members.contains(&p.id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{deps_beyond_depth, glob_match, ExternalCrates};
+    use crate::commands::generate::tests::fixture_metadata;
+
+    fn external_crates() -> ExternalCrates {
+        let mut external = ExternalCrates::default();
+        external.0.insert("dep_crate".to_owned(), "https://docs.rs/dep-crate/1.0.0/".to_owned());
+        external
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("syn", "syn"));
+        assert!(!glob_match("syn", "synstructure"));
+        assert!(glob_match("windows-*", "windows-sys"));
+        assert!(glob_match("windows*", "windows"));
+        assert!(!glob_match("windows-*", "windows"));
+        assert!(glob_match("*-sys", "libsqlite3-sys"));
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(glob_match("pr?c-macro?", "proc-macro2"));
+        assert!(!glob_match("*-sys", "sys-info"));
+    }
+
+    #[test]
+    fn test_insert_only_crates_io_packages() {
+        let metadata = fixture_metadata();
+        let package = |name: &str| metadata.packages.iter().find(|p| p.name == name).unwrap();
+
+        let mut external = ExternalCrates::default();
+        assert!(!external.insert(package("a")));
+        assert!(!external.insert(package("local")));
+        assert!(!external.insert(package("git-dep")));
+        assert!(external.insert(package("dep1")));
+        // `shared` comes from the sparse crates.io index.
+        assert!(external.insert(package("shared")));
+        let mut crate_dirs = external.crate_dirs().collect::<Vec<_>>();
+        crate_dirs.sort_unstable();
+        assert_eq!(crate_dirs, ["dep1", "shared"]);
+    }
+
+    #[test]
+    fn test_deps_beyond_depth() {
+        let metadata = fixture_metadata();
+        let package = |name: &str| metadata.packages.iter().find(|p| p.name == name).unwrap();
+        let deps_beyond_depth = |packages: &[&str], depth| {
+            let packages = packages.iter().map(|name| package(name)).collect::<Vec<_>>();
+            deps_beyond_depth(&metadata, &packages, depth).into_iter().map(|p| p.name.as_str()).collect::<Vec<_>>()
+        };
+
+        // `shared` is reached at depths 1 and 2, and kept at depth 1. The development and build
+        // dependencies of `a` are not documented, so they are never left out.
+        assert_eq!(deps_beyond_depth(&["a"], 1), ["deep", "git-dep"]);
+        assert_eq!(deps_beyond_depth(&["a"], 0), ["dep1", "shared", "deep", "local", "git-dep"]);
+        assert!(deps_beyond_depth(&["a"], 2).is_empty());
+        // Workspace members are never left out, even when they are dependencies.
+        assert_eq!(deps_beyond_depth(&["app"], 0), ["dep1", "shared", "deep", "local", "git-dep"]);
+        assert_eq!(deps_beyond_depth(&["a", "b"], 0), ["dep1", "shared", "deep", "local", "git-dep", "b-dep"]);
+
+        let mut unresolved = metadata.clone();
+        unresolved.resolve = None;
+        assert!(super::deps_beyond_depth(&unresolved, &[package("a")], 0).is_empty());
+    }
+
+    #[test]
+    fn test_rewrite_links() {
+        let html = r#"<a href="../dep_crate/struct.Foo.html#method.new">Foo</a><a href="../other/index.html">Other</a><a href="struct.Bar.html">Bar</a>"#;
+        assert_eq!(
+            external_crates().rewrite_links(html, Path::new("fixture/inner/index.html")),
+            html.to_owned()
+        );
+        assert_eq!(
+            external_crates().rewrite_links(html, Path::new("fixture/index.html")),
+            r#"<a href="https://docs.rs/dep-crate/1.0.0/dep_crate/struct.Foo.html#method.new">Foo</a><a href="../other/index.html">Other</a><a href="struct.Bar.html">Bar</a>"#
+        );
+    }
+
+    #[test]
+    fn test_rewrite_links_keeps_other_links() {
+        let external = external_crates();
+        let page = Path::new("fixture/index.html");
+        assert_eq!(
+            external.external_url("../src/dep_crate/lib.rs.html#10", page).unwrap(),
+            "https://docs.rs/dep-crate/1.0.0/src/dep_crate/lib.rs.html#10"
+        );
+        assert!(external.external_url("https://docs.rs/dep_crate/index.html", page).is_none());
+        assert!(external.external_url("#method.new", page).is_none());
+        assert!(external.external_url("../../dep_crate/index.html", page).is_none());
+        assert!(external.external_url("../static.files/main.js", page).is_none());
+    }
+}
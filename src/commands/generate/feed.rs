@@ -0,0 +1,92 @@
+//! Feeds docset browsers can subscribe to, in order to install and update a docset hosted on a
+//! web server.
+//!
+//! Dash feeds are described at <https://kapeli.com/docsets#dashdocsetfeed>. The JSON file carries
+//! the same information for Zeal and other tools.
+
+use crate::error::*;
+
+use serde_json::json;
+use snafu::ResultExt;
+
+use std::{fs::write, path::Path};
+
+/// Return the URL an archive is downloaded from, given the base URL it is published under.
+fn archive_url(base_url: &str, archive_path: &Path) -> String {
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        archive_path.file_name().unwrap_or_default().to_string_lossy()
+    )
+}
+
+/// Escape the characters which are not allowed in XML text.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Return the Dash feed of a docset.
+fn dash_feed(version: &str, url: &str) -> String {
+    format!(
+        "<entry>
+    <version>{}</version>
+    <url>{}</url>
+</entry>
+",
+        xml_escape(version),
+        xml_escape(url)
+    )
+}
+
+/// Write the Dash XML feed and the JSON feed of a docset archive next to it.
+pub fn write_feeds(archive_path: &Path, docset_name: &str, version: &str, base_url: &str) -> Result<()> {
+    let url = archive_url(base_url, archive_path);
+    write(archive_path.with_extension("xml"), dash_feed(version, &url)).context(IoWriteSnafu)?;
+
+    let json_feed = json!({
+        "name": archive_path.file_stem().unwrap_or_default().to_string_lossy(),
+        "title": docset_name,
+        "version": version,
+        "urls": [url]
+    });
+    let json_feed = serde_json::to_string_pretty(&json_feed).expect("Could not serialize the JSON feed");
+    write(archive_path.with_extension("json"), json_feed).context(IoWriteSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::read_to_string, path::Path};
+
+    use super::{archive_url, dash_feed, write_feeds};
+
+    #[test]
+    fn test_archive_url() {
+        let archive_path = Path::new("target/docset/my-crate.tgz");
+        let url = "https://example.com/docsets/my-crate.tgz";
+        assert_eq!(archive_url("https://example.com/docsets/", archive_path), url);
+        assert_eq!(archive_url("https://example.com/docsets", archive_path), url);
+    }
+
+    #[test]
+    fn test_dash_feed_escapes_urls() {
+        assert!(dash_feed("1.2.0", "https://example.com/?a=1&b=2").contains("<url>https://example.com/?a=1&amp;b=2</url>"));
+    }
+
+    #[test]
+    fn test_write_feeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("my-crate.tgz");
+        write_feeds(&archive_path, "My crate", "1.2.0", "https://example.com/docsets").unwrap();
+
+        assert_eq!(
+            read_to_string(dir.path().join("my-crate.xml")).unwrap(),
+            "<entry>\n    <version>1.2.0</version>\n    <url>https://example.com/docsets/my-crate.tgz</url>\n</entry>\n"
+        );
+        let json = read_to_string(dir.path().join("my-crate.json")).unwrap();
+        let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(json["name"], "my-crate");
+        assert_eq!(json["title"], "My crate");
+        assert_eq!(json["version"], "1.2.0");
+        assert_eq!(json["urls"][0], "https://example.com/docsets/my-crate.tgz");
+    }
+}
@@ -0,0 +1,264 @@
+//! Minimal helpers to scan the HTML pages generated by rustdoc.
+//!
+//! Rustdoc output is regular enough that we don't need a full HTML parser to find the few elements
+//! we are interested in.
+
+use std::path::{Path, PathBuf};
+
+/// Iterate over the `id` attributes of the elements of an HTML document. Yields the byte offset
+/// of the start of the element's opening tag, the name of the element and the value of the `id`
+/// attribute.
+pub fn element_ids(html: &str) -> impl Iterator<Item = (usize, &str, &str)> {
+    html.match_indices(" id=\"").filter_map(move |(start, pattern)| {
+        let id_start = start + pattern.len();
+        let id = &html[id_start..id_start + html[id_start..].find('"')?];
+        let tag_start = html[..start].rfind('<')?;
+        let tag_name = html[tag_start + 1..start].split_whitespace().next().unwrap_or_default();
+        Some((tag_start, tag_name, id))
+    })
+}
+
+/// Remove the numeric suffix rustdoc uses to disambiguate duplicate anchors, e.g. `add-1`.
+pub fn strip_disambiguator(id: &str) -> &str {
+    match id.rsplit_once('-') {
+        Some((id, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => id,
+        _ => id
+    }
+}
+
+/// Return the HTML content of the element whose opening tag starts at `tag_start`. Nested
+/// elements of the same type are not supported.
+pub fn element_html<'a>(html: &'a str, tag_start: usize, tag_name: &str) -> Option<&'a str> {
+    let content_start = tag_start + html[tag_start..].find('>')? + 1;
+    let content_len = html[content_start..].find(&format!("</{}>", tag_name))?;
+    Some(&html[content_start..content_start + content_len])
+}
+
+/// Return the HTML content of the first docblock of a page, i.e. the top-level documentation of
+/// the item it documents.
+pub fn docblock(html: &str) -> Option<&str> {
+    let tag_start = html[..html.find("class=\"docblock\"")?].rfind('<')?;
+    let content_start = tag_start + html[tag_start..].find('>')? + 1;
+    // Docblocks contain nested `div`s, e.g. around code examples.
+    let mut depth = 1;
+    let mut pos = content_start;
+    while depth > 0 {
+        let next_open = html[pos..].find("<div").map(|i| pos + i);
+        let next_close = pos + html[pos..].find("</div>")?;
+        match next_open {
+            Some(open) if open < next_close => {
+                depth += 1;
+                pos = open + "<div".len();
+            }
+            _ => {
+                depth -= 1;
+                pos = next_close + "</div>".len();
+            }
+        }
+    }
+    Some(&html[content_start..pos - "</div>".len()])
+}
+
+/// Return the text content of the element whose opening tag starts at `tag_start`, with nested
+/// tags removed and the most common character entities decoded.
+pub fn element_text(html: &str, tag_start: usize, tag_name: &str) -> Option<String> {
+    let text = strip_tags(element_html(html, tag_start, tag_name)?);
+    Some(text.trim_matches(|c: char| c.is_whitespace() || c == '§').to_owned())
+}
+
+/// Remove the tags from an HTML fragment and decode the most common character entities.
+pub fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => ()
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Return the value of an attribute of the first element of an HTML fragment with this attribute.
+fn attribute<'a>(html: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!(" {}=\"", name);
+    let value_start = html.find(&pattern)? + pattern.len();
+    Some(&html[value_start..value_start + html[value_start..].find('"')?])
+}
+
+/// Return the re-exports listed in the "Re-exports" section of a crate or module page, as their
+/// name, the kind of the re-exported item, e.g. `struct`, and the link to its page. Glob
+/// re-exports, which have no name, are not returned.
+pub fn reexports(html: &str) -> Vec<(&str, &str, &str)> {
+    element_ids(html)
+        .filter_map(|(tag_start, tag_name, id)| {
+            let name = id.strip_prefix("reexport.")?;
+            // The item is linked to in the `pub use` declaration, e.g. `pub use inner::Foo as Bar;`.
+            let declaration = element_html(html, tag_start, tag_name)?;
+            let link = &declaration[declaration.find("<a ")?..];
+            let link = &link[..link.find('>')?];
+            Some((name, attribute(link, "class")?, attribute(link, "href")?))
+        })
+        .collect()
+}
+
+/// Return the name of a trait implementation from the HTML of its header, e.g.
+/// `impl<T> Display for my_crate::Foo<T>`. The name is the text of the header up to its where
+/// clause, if any. The implementing type is qualified using the title of the link rustdoc
+/// generates for it. Inherent impls have no name.
+pub fn impl_name(header_html: &str) -> Option<String> {
+    // Where clauses mention other types, and are not part of the name.
+    let header_html = match header_html.find("class=\"where") {
+        Some(where_start) => &header_html[..header_html[..where_start].rfind('<')?],
+        None => header_html
+    };
+    let text = strip_tags(header_html);
+    let text = text.trim_matches(|c: char| c.is_whitespace() || c == '§');
+    // Older rustdoc versions render where clauses as plain text.
+    let text = text
+        .match_indices("where")
+        .find(|(i, w)| {
+            text[..*i].ends_with(char::is_whitespace)
+                && text[i + w.len()..].chars().next().is_none_or(char::is_whitespace)
+        })
+        .map_or(text, |(i, _)| &text[..i]);
+    let (impl_trait, self_ty) = text.strip_prefix("impl")?.split_once(" for ")?;
+    let self_ty = self_ty.trim_end();
+
+    // Links are titled with the kind and the path of their target, e.g. `struct my_crate::Foo`.
+    let self_ty_html = &header_html[header_html.find(" for ")? + " for ".len()..];
+    let qualified_self_ty = self_ty_html.strip_prefix("<a ").and_then(|link| {
+        let (_kind, path) = attribute(link, "title")?.split_once(' ')?;
+        let link_text = strip_tags(element_html(self_ty_html, 0, "a")?);
+        Some(format!("{}{}", path, self_ty.strip_prefix(link_text.as_str())?))
+    });
+    Some(format!("impl{} for {}", impl_trait, qualified_self_ty.as_deref().unwrap_or(self_ty)))
+}
+
+/// Resolve a relative link of a page to a path relative to the rustdoc root directory, given the
+/// path of the page relative to it. Fragments and queries are not part of the resolved path.
+/// Absolute links, and links pointing outside of the rustdoc root directory, are not resolved.
+pub fn resolve_href(page_path: &Path, href: &str) -> Option<PathBuf> {
+    if href.contains(':') || href.starts_with(['#', '/', '?']) {
+        return None;
+    }
+    let mut resolved = page_path.parent()?.to_owned();
+    for segment in href.split(['#', '?']).next()?.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            segment => resolved.push(segment)
+        }
+    }
+    Some(resolved)
+}
+
+/// Percent-encode a string for use in a URL fragment.
+pub fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~:".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// Decode a percent-encoded string, as found in rustdoc anchors, e.g. `Add%3Cu8%3E`.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::generate::tests::read_fixture;
+
+    #[test]
+    fn test_element_ids() {
+        let html = r#"<div class="a"><section id="method.new" class="method"><span class="x" id="structfield.y">"#;
+        let ids = element_ids(html).collect::<Vec<_>>();
+        assert_eq!(ids, vec![(15, "section", "method.new"), (55, "span", "structfield.y")]);
+    }
+
+    #[test]
+    fn test_element_text() {
+        let html = r##"<h2 id="fields" class="section-header">Fields &amp; more<a href="#fields" class="anchor">§</a></h2>"##;
+        assert_eq!(element_text(html, 0, "h2").unwrap(), "Fields & more");
+    }
+
+    #[test]
+    fn test_docblock() {
+        // The docblock contains nested `div`s around the code examples, and stops before the
+        // sections listing the items of the crate.
+        let html = read_fixture("fixture/index.html");
+        let top_doc = docblock(&html).unwrap();
+        assert!(top_doc.starts_with("<p>A fixture crate.</p>"));
+        assert!(top_doc.contains("<div class=\"example-wrap\">"));
+        assert!(top_doc.trim_end().ends_with("<p>There are none.</p>"), "Unexpected docblock {}", top_doc);
+        assert!(docblock(&read_fixture("fixture/enum.MyEnum.html")).is_none());
+    }
+
+    #[test]
+    fn test_impl_name() {
+        let header = r##"<a href="#impl-MyTrait-for-MyStruct" class="anchor">§</a><h3 class="code-header">impl <a class="trait" href="trait.MyTrait.html" title="trait fixture::MyTrait">MyTrait</a> for <a class="struct" href="struct.MyStruct.html" title="struct fixture::MyStruct">MyStruct</a></h3>"##;
+        assert_eq!(impl_name(header).unwrap(), "impl MyTrait for fixture::MyStruct");
+        assert_eq!(impl_name("impl&lt;T&gt; Clone for Foo&lt;T&gt;").unwrap(), "impl<T> Clone for Foo<T>");
+        assert!(impl_name(r#"<h3 class="code-header">impl <a class="struct" title="struct a::Foo">Foo</a></h3>"#).is_none());
+        let header = r#"<h3 class="code-header">impl&lt;T&gt; <a class="trait" href="trait.MyTrait.html" title="trait fixture::MyTrait">MyTrait</a> for <a class="struct" href="struct.Wrapper.html" title="struct fixture::Wrapper">Wrapper</a>&lt;T&gt;</h3>"#;
+        assert_eq!(impl_name(header).unwrap(), "impl<T> MyTrait for fixture::Wrapper<T>");
+    }
+
+    #[test]
+    fn test_impl_name_of_blanket_impl() {
+        // The types of the where clause are not taken for the implementing type.
+        let header = r#"<h3 class="code-header">impl&lt;T, U&gt; <a class="trait" href="https://doc.rust-lang.org/nightly/core/convert/trait.Into.html" title="trait core::convert::Into">Into</a>&lt;U&gt; for T<div class="where">where
+    U: <a class="trait" href="https://doc.rust-lang.org/nightly/core/convert/trait.From.html" title="trait core::convert::From">From</a>&lt;T&gt;,</div></h3>"#;
+        assert_eq!(impl_name(header).unwrap(), "impl<T, U> Into<U> for T");
+        let header = r#"<h3 class="code-header">impl&lt;T&gt; <a class="trait" href="trait.MyTrait.html" title="trait fixture::MyTrait">MyTrait</a> for T <span class="where fmt-newline">where T: <a class="trait" href="https://doc.rust-lang.org/nightly/core/fmt/trait.Debug.html" title="trait core::fmt::Debug">Debug</a></span></h3>"#;
+        assert_eq!(impl_name(header).unwrap(), "impl<T> MyTrait for T");
+        assert_eq!(impl_name("impl&lt;T&gt; MyTrait for T where T: Debug").unwrap(), "impl<T> MyTrait for T");
+    }
+
+    #[test]
+    fn test_percent_encoding_roundtrip() {
+        assert_eq!(percent_decode("Add%3C%26u8%3E-for-u8"), "Add<&u8>-for-u8");
+        assert_eq!(percent_encode("impl Add<&u8> for u8"), "impl%20Add%3C%26u8%3E%20for%20u8");
+        assert_eq!(percent_decode(&percent_encode("a::B<C> d")), "a::B<C> d");
+    }
+
+    #[test]
+    fn test_strip_disambiguator() {
+        assert_eq!(strip_disambiguator("method.add-1"), "method.add");
+        assert_eq!(strip_disambiguator("impl-Foo-for-Bar"), "impl-Foo-for-Bar");
+        assert_eq!(strip_disambiguator("method.add-"), "method.add-");
+    }
+}
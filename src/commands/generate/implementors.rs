@@ -0,0 +1,131 @@
+//! Entries for trait implementations, which are listed on the pages of the implementing types and
+//! of the implemented traits.
+
+use crate::error::*;
+
+use super::{html, DocsetEntry, EntryType};
+
+use snafu::ResultExt;
+
+use std::{
+    ffi::OsStr,
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf}
+};
+
+/// Directories in which rustdoc stores the implementors of each trait that are defined in other
+/// crates, depending on the rustdoc version.
+const IMPLEMENTORS_DIRS: &[&str] = &["implementors", "trait.impl"];
+
+/// Return the entry of the impl block whose anchor starts at `tag_start` on the page documented by
+/// `page_entry`. Impls on type pages are indexed as instances, while the implementors listed on
+/// trait pages are indexed as protocols.
+pub fn impl_entry(
+    page_html: &str,
+    tag_start: usize,
+    tag_name: &str,
+    id: &str,
+    page_entry: &DocsetEntry
+) -> Option<DocsetEntry> {
+    let name = html::impl_name(html::element_html(page_html, tag_start, tag_name)?)?;
+    let ty = if page_entry.ty == EntryType::Trait { EntryType::Implementor } else { EntryType::Impl };
+    Some(DocsetEntry::new(name, ty, page_entry.path.clone(), Some(id.to_owned())))
+}
+
+/// Extract the impl headers from an implementors file. The headers are stored as JavaScript string
+/// literals, in a structure which depends on the rustdoc version.
+fn parse_implementors_js(js: &str) -> Vec<String> {
+    let mut headers = vec![];
+    let mut rest = js;
+    while let Some(start) = rest.find("\"impl") {
+        let literal = &rest[start + 1..];
+        let mut header = String::new();
+        let mut chars = literal.char_indices();
+        let mut end = literal.len();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => header.extend(chars.next().map(|(_, c)| c)),
+                '"' => {
+                    end = i;
+                    break;
+                }
+                c => header.push(c)
+            }
+        }
+        headers.push(header);
+        rest = &literal[end..];
+    }
+    headers
+}
+
+fn recursive_walk_implementors(
+    implementors_dir: &Path,
+    cur_dir: &Path,
+    rustdoc_root_dir: &Path,
+    entries: &mut Vec<DocsetEntry>
+) -> Result<()> {
+    for dir_entry in read_dir(cur_dir).context(IoReadSnafu)? {
+        let path = dir_entry.context(IoReadSnafu)?.path();
+        if path.is_dir() {
+            recursive_walk_implementors(implementors_dir, &path, rustdoc_root_dir, entries)?;
+            continue;
+        }
+        if path.extension() != Some(OsStr::new("js")) {
+            continue;
+        }
+        // The implementors of a trait are stored at the same path as the trait page.
+        let trait_page = path.strip_prefix(implementors_dir).unwrap().with_extension("html");
+        if !rustdoc_root_dir.join(&trait_page).is_file() {
+            continue;
+        }
+        for header in parse_implementors_js(&read_to_string(&path).context(IoReadSnafu)?) {
+            if let Some(name) = html::impl_name(&header) {
+                let entry = DocsetEntry::new(
+                    name,
+                    EntryType::Implementor,
+                    trait_page.clone(),
+                    Some("implementors".to_owned())
+                );
+                if !entries.contains(&entry) {
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return the entries for the implementors of traits which rustdoc lists in separate files.
+pub fn implementor_entries(rustdoc_root_dir: &Path) -> Result<Vec<DocsetEntry>> {
+    let mut entries = vec![];
+    for dir in IMPLEMENTORS_DIRS {
+        let implementors_dir: PathBuf = rustdoc_root_dir.join(dir);
+        if implementors_dir.is_dir() {
+            recursive_walk_implementors(&implementors_dir, &implementors_dir, rustdoc_root_dir, &mut entries)?;
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{implementor_entries, parse_implementors_js};
+    use crate::commands::generate::{tests::fixture_rustdoc_dir, EntryType};
+
+    #[test]
+    fn test_parse_implementors_js() {
+        let js = r#"(function() {var implementors = Object.fromEntries([["a",[["impl <a class=\"trait\" title=\"trait b::T\">T</a> for <a class=\"struct\" title=\"struct a::S\">S</a>",0,["a::S"]]]]]);})()"#;
+        assert_eq!(parse_implementors_js(js), vec![
+            r#"impl <a class="trait" title="trait b::T">T</a> for <a class="struct" title="struct a::S">S</a>"#.to_owned()
+        ]);
+    }
+
+    #[test]
+    fn test_implementor_entries() {
+        let entries = implementor_entries(&fixture_rustdoc_dir()).unwrap();
+        assert_eq!(entries.len(), 1, "Unexpected entries {:?}", entries);
+        assert_eq!(entries[0].name, "impl MyTrait for other::Other");
+        assert_eq!(entries[0].ty, EntryType::Implementor);
+        assert_eq!(entries[0].index_path(), "fixture/trait.MyTrait.html#implementors");
+    }
+}
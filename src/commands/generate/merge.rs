@@ -0,0 +1,263 @@
+//! Merging of the documentation built for several target triples into a single docset.
+//!
+//! Files which are identical for all the targets are only stored once. Files which differ are
+//! stored side by side, the variants of the later targets being suffixed with their triple, e.g.
+//! `struct.Foo.x86_64-pc-windows-gnu.html` or `sidebar-items.x86_64-pc-windows-gnu.js`, so that
+//! relative links keep working. The references of the pages to the files stored under a suffixed
+//! path are rewritten, so that the pages of each target use their own version of the scripts and
+//! link to their own version of the other pages. Pages shared by several targets use the version
+//! of the first of them.
+
+use crate::error::*;
+
+use super::{cargo_doc::DocumentedCrates, external::ExternalCrates, html, write_doc_file, DocsetEntry, EntryTargets};
+
+use snafu::ResultExt;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::OsStr,
+    fs::{create_dir_all, read, read_dir},
+    path::{Path, PathBuf}
+};
+
+/// The documentation built for a target triple.
+pub struct TargetDocs {
+    pub target: String,
+    pub rustdoc_root_dir: PathBuf,
+    pub entries: Vec<DocsetEntry>
+}
+
+/// A version of a file shared by one or several targets.
+struct Variant {
+    contents: Vec<u8>,
+    path: PathBuf,
+    targets: Vec<usize>
+}
+
+/// The path under which each target's version of a file is stored, keyed by the index of the
+/// target and the original path of the file.
+type StoredPaths = HashMap<(usize, PathBuf), PathBuf>;
+
+/// Return the path under which the variant of a file specific to the given target is stored.
+fn variant_path(path: &Path, target: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}.{}", stem, target, extension))
+}
+
+/// Rewrite the relative references of a page of the given target to the files whose version for
+/// this target is stored under a suffixed path. `page_path` is the original path of the page.
+fn rewrite_variant_refs(html: &str, page_path: &Path, target: usize, stored_paths: &StoredPaths) -> String {
+    let mut rewritten = String::with_capacity(html.len());
+    let mut rest = html;
+    // References are found in attributes, e.g. `href`, `src` or the `data-` ones of rustdoc.
+    while let Some(start) = rest.find("=\"") {
+        let value_start = start + "=\"".len();
+        let value_len = match rest[value_start..].find('"') {
+            Some(len) => len,
+            None => break
+        };
+        let value = &rest[value_start..value_start + value_len];
+        rewritten.push_str(&rest[..value_start]);
+        let stored_name = html::resolve_href(page_path, value).and_then(|resolved| {
+            let stored = stored_paths.get(&(target, resolved.clone()))?;
+            (*stored != resolved).then(|| stored.file_name().unwrap().to_string_lossy())
+        });
+        match stored_name {
+            // Variants are stored in the same directory as the original file.
+            Some(stored_name) => {
+                let path_end = value.find(['#', '?']).unwrap_or(value.len());
+                let name_start = value[..path_end].rfind('/').map_or(0, |i| i + 1);
+                rewritten.push_str(&value[..name_start]);
+                rewritten.push_str(&stored_name);
+                rewritten.push_str(&value[path_end..]);
+            }
+            None => rewritten.push_str(value)
+        }
+        rest = &rest[value_start + value_len..];
+    }
+    rewritten.push_str(rest);
+    rewritten
+}
+
+fn collect_files(
+    root_dir: &Path,
+    cur_dir: &Path,
+    documented: &DocumentedCrates,
+    files: &mut BTreeSet<PathBuf>
+) -> Result<()> {
+    for dir_entry in read_dir(cur_dir).context(IoReadSnafu)? {
+        let path = dir_entry.context(IoReadSnafu)?.path();
+        let relative_path = path.strip_prefix(root_dir).unwrap();
+        if documented.is_stale(root_dir, relative_path) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root_dir, &path, documented, files)?;
+        } else if path.is_file() {
+            files.insert(relative_path.to_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Copy the documentation of all the targets to `documents_dir`, and return the entries of the
+/// merged docset along with the targets each of them is available on.
+pub fn merge_target_docs(
+    target_docs: &[TargetDocs],
+    documented: &DocumentedCrates,
+    external: &ExternalCrates,
+    documents_dir: &Path
+) -> Result<(Vec<DocsetEntry>, EntryTargets)> {
+    let mut files = BTreeSet::new();
+    for docs in target_docs {
+        collect_files(&docs.rustdoc_root_dir, &docs.rustdoc_root_dir, documented, &mut files)?;
+    }
+
+    let mut stored_paths = StoredPaths::new();
+    // Pages are written once the paths of all the files are known, as they refer to them.
+    let mut pages = vec![];
+    for file in files {
+        let mut variants: Vec<Variant> = vec![];
+        for (i, docs) in target_docs.iter().enumerate() {
+            let src = docs.rustdoc_root_dir.join(&file);
+            if !src.is_file() {
+                continue;
+            }
+            let contents = read(&src).context(IoReadSnafu)?;
+            match variants.iter_mut().find(|v| v.contents == contents) {
+                Some(variant) => variant.targets.push(i),
+                None => {
+                    let path = if variants.is_empty() { file.clone() } else { variant_path(&file, &docs.target) };
+                    variants.push(Variant { contents, path, targets: vec![i] });
+                }
+            }
+        }
+
+        let is_page = file.extension() == Some(OsStr::new("html"));
+        for variant in variants {
+            for i in &variant.targets {
+                stored_paths.insert((*i, file.clone()), variant.path.clone());
+            }
+            let dst = documents_dir.join(&variant.path);
+            create_dir_all(dst.parent().unwrap()).context(IoWriteSnafu)?;
+            if is_page {
+                pages.push((dst, file.clone(), variant.targets[0]));
+            } else {
+                let root_dir = &target_docs[variant.targets[0]].rustdoc_root_dir;
+                let keep_crate = |name: &str| !documented.is_stale(root_dir, Path::new(name));
+                write_doc_file(&dst, &file, variant.contents, &keep_crate, &|_, html| html.to_owned())?;
+            }
+        }
+    }
+    for (dst, file, i) in pages {
+        let contents = read(target_docs[i].rustdoc_root_dir.join(&file)).context(IoReadSnafu)?;
+        write_doc_file(&dst, &file, contents, &|_| true, &|path, html| {
+            rewrite_variant_refs(&external.rewrite_links(html, path), path, i, &stored_paths)
+        })?;
+    }
+
+    let mut entries = vec![];
+    let mut entry_targets = EntryTargets::new();
+    for (i, docs) in target_docs.iter().enumerate() {
+        for entry in &docs.entries {
+            let mut entry = entry.clone();
+            if let Some(path) = stored_paths.get(&(i, entry.path.clone())) {
+                entry.path = path.clone();
+            }
+            let targets = entry_targets.entry(entry.clone()).or_default();
+            if targets.is_empty() {
+                entries.push(entry);
+            }
+            if !targets.contains(&docs.target) {
+                targets.push(docs.target.clone());
+            }
+        }
+    }
+    Ok((entries, entry_targets))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{read_to_string, remove_file, write},
+        path::Path
+    };
+
+    use super::{merge_target_docs, variant_path, DocumentedCrates, ExternalCrates, TargetDocs};
+    use crate::{
+        commands::generate::{tests::copy_fixture, walk_rustdoc_dir},
+        Reexports
+    };
+
+    const LINUX: &str = "x86_64-unknown-linux-gnu";
+    const WINDOWS: &str = "x86_64-pc-windows-gnu";
+
+    #[test]
+    fn test_variant_path() {
+        assert_eq!(
+            variant_path(Path::new("a/struct.Foo.html"), WINDOWS),
+            Path::new("a/struct.Foo.x86_64-pc-windows-gnu.html")
+        );
+    }
+
+    /// Copy the fixture rustdoc output as the documentation of a target, after altering it.
+    fn target_docs(root_dir: &Path, target: &str, alter: impl Fn(&Path)) -> TargetDocs {
+        let rustdoc_root_dir = root_dir.join(target).join("doc");
+        copy_fixture(&rustdoc_root_dir);
+        alter(&rustdoc_root_dir.join("fixture"));
+        let entries = walk_rustdoc_dir(&rustdoc_root_dir, Reexports::Canonical)
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.fragment.is_none())
+            .collect();
+        TargetDocs { target: target.to_owned(), rustdoc_root_dir, entries }
+    }
+
+    #[test]
+    fn test_merge_target_docs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let linux = target_docs(tmp.path(), LINUX, |dir| remove_file(dir.join("fn.my_function.html")).unwrap());
+        let windows = target_docs(tmp.path(), WINDOWS, |dir| {
+            let page = read_to_string(dir.join("struct.MyStruct.html")).unwrap();
+            write(dir.join("struct.MyStruct.html"), page.replace("A struct.", "A Windows struct.")).unwrap();
+            let sidebar = read_to_string(dir.join("sidebar-items.js")).unwrap();
+            write(dir.join("sidebar-items.js"), sidebar.replace("\"MyStruct\"", "\"MyStruct\",\"Windows\"")).unwrap();
+        });
+        let documents_dir = tmp.path().join("Documents");
+
+        let (entries, entry_targets) = merge_target_docs(
+            &[linux, windows],
+            &DocumentedCrates::default(),
+            &ExternalCrates::default(),
+            &documents_dir
+        )
+        .unwrap();
+
+        let expected: &[(&str, &str, &[&str])] = &[
+            ("fixture::MyEnum", "fixture/enum.MyEnum.html", &[LINUX, WINDOWS]),
+            ("fixture::MyStruct", "fixture/struct.MyStruct.html", &[LINUX]),
+            ("fixture::MyStruct", "fixture/struct.MyStruct.x86_64-pc-windows-gnu.html", &[WINDOWS]),
+            ("fixture::my_function", "fixture/fn.my_function.html", &[WINDOWS])
+        ];
+        for (name, path, targets) in expected {
+            let entry = entries.iter().find(|e| &e.name == name && &e.index_path() == path);
+            assert!(entry.is_some(), "Expected entry {} at {} in {:?}", name, path, entries);
+            assert_eq!(&entry_targets[entry.unwrap()], targets);
+            assert!(documents_dir.join(path).is_file(), "Expected page {}", path);
+        }
+
+        // The scripts which differ are stored side by side, and each version of the pages uses
+        // its own.
+        let read = |path: &str| read_to_string(documents_dir.join(path)).unwrap();
+        assert!(!read("fixture/sidebar-items.js").contains("Windows"));
+        assert!(read("fixture/sidebar-items.x86_64-pc-windows-gnu.js").contains("Windows"));
+        assert!(read("fixture/struct.MyStruct.html").contains(r#"src="sidebar-items.js""#));
+        let windows_page = read("fixture/struct.MyStruct.x86_64-pc-windows-gnu.html");
+        assert!(windows_page.contains("A Windows struct."));
+        assert!(windows_page.contains(r#"src="sidebar-items.x86_64-pc-windows-gnu.js""#));
+        // Files which are identical for all the targets are stored once.
+        assert!(!documents_dir.join("search-index.x86_64-pc-windows-gnu.js").exists());
+    }
+}
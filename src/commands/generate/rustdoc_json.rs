@@ -0,0 +1,318 @@
+//! Entry source reading the JSON output of rustdoc (`--output-format json`, nightly only).
+//!
+//! Unlike the file names and the search index, the JSON output is a complete typed model of the
+//! documented crates, which lets us index members that are not visible in the HTML file tree and
+//! compute their anchors without reading the HTML pages.
+
+use crate::error::*;
+
+use super::{alias_entry, DocsetEntry, EntryType};
+
+use rustdoc_types::{Attribute, Crate, Id, Item, ItemEnum, ItemKind, StructKind, FORMAT_VERSION};
+use serde_json::Value;
+use snafu::{ensure, ResultExt};
+
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::{read_dir, File},
+    io::BufReader,
+    path::{Path, PathBuf}
+};
+
+/// Return the rustdoc page prefix of the items of the given kind, for items that have a page.
+fn page_prefix(kind: &ItemKind) -> Option<&'static str> {
+    match kind {
+        ItemKind::Constant => Some("constant"),
+        ItemKind::Enum => Some("enum"),
+        ItemKind::ExternType => Some("foreigntype"),
+        ItemKind::Function => Some("fn"),
+        ItemKind::Keyword => Some("keyword"),
+        ItemKind::Macro => Some("macro"),
+        ItemKind::Primitive => Some("primitive"),
+        ItemKind::ProcAttribute => Some("attr"),
+        ItemKind::ProcDerive => Some("derive"),
+        ItemKind::Static => Some("static"),
+        ItemKind::Struct => Some("struct"),
+        ItemKind::Trait => Some("trait"),
+        ItemKind::TraitAlias => Some("traitalias"),
+        ItemKind::TypeAlias => Some("type"),
+        ItemKind::Union => Some("union"),
+        _ => None
+    }
+}
+
+/// Return the rustdoc anchor prefix of a member item, e.g. `method` for a method of an impl block.
+fn member_prefix(item: &Item, in_trait: bool) -> Option<&'static str> {
+    match &item.inner {
+        ItemEnum::Function(f) if in_trait && !f.has_body => Some("tymethod"),
+        ItemEnum::Function(_) => Some("method"),
+        ItemEnum::AssocConst { .. } => Some("associatedconstant"),
+        ItemEnum::AssocType { .. } => Some("associatedtype"),
+        ItemEnum::StructField(_) => Some("structfield"),
+        ItemEnum::Variant(_) => Some("variant"),
+        _ => None
+    }
+}
+
+/// Return the aliases declared with `#[doc(alias = "...")]` or `#[doc(alias("...", ...))]` on an
+/// item.
+fn doc_aliases(item: &Item) -> Vec<String> {
+    let mut aliases = vec![];
+    for attr in &item.attrs {
+        // Aliases are only available in source form.
+        let args = match attr {
+            Attribute::Other(attr) => attr.strip_prefix("#[doc(alias").and_then(|a| a.strip_suffix(")]")),
+            _ => None
+        };
+        // Every other token between double quotes is an alias.
+        if let Some(args) = args {
+            aliases.extend(args.split('"').skip(1).step_by(2).map(ToOwned::to_owned));
+        }
+    }
+    aliases
+}
+
+/// Generates the member entries of a page, disambiguating duplicate anchors the same way rustdoc
+/// does.
+struct MemberEntries<'a> {
+    krate: &'a Crate,
+    page_entry: &'a DocsetEntry,
+    anchors: HashMap<String, usize>,
+    entries: Vec<DocsetEntry>
+}
+
+impl<'a> MemberEntries<'a> {
+    fn new(krate: &'a Crate, page_entry: &'a DocsetEntry) -> MemberEntries<'a> {
+        MemberEntries { krate, page_entry, anchors: HashMap::new(), entries: vec![] }
+    }
+
+    fn push(&mut self, ids: &[Id], in_trait: bool) {
+        for item in ids.iter().filter_map(|id| self.krate.index.get(id)) {
+            if let (Some(name), Some(prefix)) = (&item.name, member_prefix(item, in_trait)) {
+                self.push_member(name, prefix);
+                if let Some(entry) = self.entries.last().cloned() {
+                    self.entries.extend(doc_aliases(item).iter().map(|alias| alias_entry(alias, &entry)));
+                }
+            }
+        }
+    }
+
+    fn push_member(&mut self, name: &str, prefix: &str) {
+        let ty = match EntryType::from_rustdoc_member_prefix(prefix) {
+            Some(ty) => ty,
+            None => return
+        };
+        let mut anchor = format!("{}.{}", prefix, name);
+        let count = self.anchors.entry(anchor.clone()).or_insert(0);
+        if *count > 0 {
+            anchor.push_str(&format!("-{}", count));
+        }
+        *count += 1;
+        self.entries.push(DocsetEntry::new(
+            format!("{}::{}", self.page_entry.name, name),
+            ty,
+            self.page_entry.path.clone(),
+            Some(anchor)
+        ));
+    }
+
+    /// Add the members of impl blocks, inherent impls first like on the rustdoc pages. Blanket
+    /// and auto trait impls are skipped, as they are on the HTML pages.
+    fn push_impls(&mut self, impl_ids: &[Id]) {
+        let impls = impl_ids
+            .iter()
+            .filter_map(|id| match &self.krate.index.get(id)?.inner {
+                ItemEnum::Impl(i) if i.blanket_impl.is_none() && !i.is_synthetic => Some(i),
+                _ => None
+            })
+            .collect::<Vec<_>>();
+        for i in impls.iter().filter(|i| i.trait_.is_none()) {
+            self.push(&i.items, false);
+        }
+        for i in impls.iter().filter(|i| i.trait_.is_some()) {
+            self.push(&i.items, false);
+            // Provided methods the impl does not override are documented on the page too.
+            for name in &i.provided_trait_methods {
+                self.push_member(name, "method");
+            }
+        }
+    }
+}
+
+/// Return the member entries of an item page.
+fn member_entries(krate: &Crate, item: &Item, page_entry: &DocsetEntry) -> Vec<DocsetEntry> {
+    let mut members = MemberEntries::new(krate, page_entry);
+    match &item.inner {
+        ItemEnum::Struct(s) => {
+            if let StructKind::Plain { fields, .. } = &s.kind {
+                members.push(fields, false);
+            }
+            members.push_impls(&s.impls);
+        }
+        ItemEnum::Union(u) => {
+            members.push(&u.fields, false);
+            members.push_impls(&u.impls);
+        }
+        ItemEnum::Enum(e) => {
+            members.push(&e.variants, false);
+            members.push_impls(&e.impls);
+        }
+        ItemEnum::Trait(t) => members.push(&t.items, true),
+        ItemEnum::Primitive(p) => members.push_impls(&p.impls),
+        _ => ()
+    }
+    members.entries
+}
+
+/// Return the entries of a crate documented in the rustdoc JSON format.
+fn crate_entries(krate: &Crate) -> Vec<DocsetEntry> {
+    let mut paths = krate
+        .paths
+        .iter()
+        // Only index the items of the documented crate, which are the ones with a page.
+        .filter(|(id, summary)| summary.crate_id == 0 && krate.index.contains_key(id))
+        .collect::<Vec<_>>();
+    paths.sort_by(|(_, a), (_, b)| a.path.cmp(&b.path));
+
+    let mut entries = vec![];
+    for (id, summary) in paths {
+        let name = summary.path.join("::");
+        let mut page = summary.path.iter().collect::<PathBuf>();
+        let entry = match (&summary.kind, page_prefix(&summary.kind)) {
+            (ItemKind::Module, _) => {
+                page.push("index.html");
+                let ty = if summary.path.len() == 1 { EntryType::Package } else { EntryType::Module };
+                DocsetEntry::new(name, ty, page, None)
+            }
+            (_, Some(prefix)) => {
+                let item_name = page.file_name().unwrap().to_string_lossy().to_string();
+                page.set_file_name(format!("{}.{}.html", prefix, item_name));
+                let ty = match EntryType::from_rustdoc_prefix(prefix) {
+                    Some(ty) => ty,
+                    None => continue
+                };
+                DocsetEntry::new(name, ty, page, None)
+            }
+            _ => continue
+        };
+        let item = &krate.index[id];
+        if entry.ty.has_members() {
+            entries.extend(member_entries(krate, item, &entry));
+        }
+        entries.extend(doc_aliases(item).iter().map(|alias| alias_entry(alias, &entry)));
+        entries.push(entry);
+    }
+    entries
+}
+
+/// Read a crate documented in the rustdoc JSON format.
+fn read_crate(json_path: &Path) -> Result<Crate> {
+    let file = File::open(json_path).context(IoReadSnafu)?;
+    let json: Value = serde_json::from_reader(BufReader::new(file)).context(RustdocJsonSnafu)?;
+    let format_version = json.get("format_version").and_then(Value::as_u64).unwrap_or_default();
+    ensure!(
+        format_version == u64::from(FORMAT_VERSION),
+        RustdocJsonFormatSnafu {
+            path: json_path.to_owned(),
+            version: format_version
+        }
+    );
+    serde_json::from_value(json).context(RustdocJsonSnafu)
+}
+
+/// Generate the docset entries from the rustdoc JSON files found in a directory.
+pub fn rustdoc_json_entries(json_dir: &Path) -> Result<Vec<DocsetEntry>> {
+    let mut entries = vec![];
+    for dir_entry in read_dir(json_dir).context(IoReadSnafu)? {
+        let path = dir_entry.context(IoReadSnafu)?.path();
+        if path.is_file() && path.extension() == Some(OsStr::new("json")) {
+            entries.extend(crate_entries(&read_crate(&path)?));
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{doc_aliases, rustdoc_json_entries};
+    use crate::{
+        commands::generate::{tests::fixture_rustdoc_dir, walk_rustdoc_dir, DocsetEntry, EntryType},
+        Reexports
+    };
+
+    /// Return the directory of the fixture rustdoc JSON output.
+    fn fixture_json_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/json")
+    }
+
+    fn fixture_entries() -> Vec<DocsetEntry> {
+        rustdoc_json_entries(&fixture_json_dir()).unwrap()
+    }
+
+    /// Return the entries found in the fixture rustdoc HTML output of the same crate.
+    fn html_fixture_entries() -> Vec<DocsetEntry> {
+        walk_rustdoc_dir(&fixture_rustdoc_dir(), Reexports::Canonical).unwrap()
+    }
+
+    #[test]
+    fn test_rustdoc_json_page_entries() {
+        // Every page of the crate is found, with the same name and type as from the HTML output.
+        let entries = fixture_entries();
+        let from_filenames = html_fixture_entries();
+        let pages = entries.iter().filter(|e| e.fragment.is_none() && !e.name.contains(" (→ ")).collect::<Vec<_>>();
+        for entry in &pages {
+            assert!(from_filenames.contains(entry), "Unexpected entry {:?}", entry);
+        }
+        // The HTML output also documents the items of std, the procedural macros and a re-export.
+        let html_pages = from_filenames
+            .iter()
+            .filter(|e| e.fragment.is_none() && e.name.starts_with("fixture"))
+            .filter(|e| !matches!(e.ty, EntryType::Attribute | EntryType::Derive) && e.name != "fixture::Hidden")
+            .collect::<Vec<_>>();
+        assert_eq!(pages.len(), html_pages.len(), "Unexpected entries in {:?}", pages);
+    }
+
+    #[test]
+    fn test_rustdoc_json_member_entries() {
+        // The members are the ones found in the HTML output, with the methods of trait
+        // implementations the HTML fixture doesn't show.
+        let entries = fixture_entries();
+        let from_filenames = html_fixture_entries();
+        let members = entries
+            .iter()
+            .filter(|e| e.fragment.is_some() && !e.name.contains(" (→ "))
+            .collect::<Vec<_>>();
+        let html_members = from_filenames
+            .iter()
+            .filter(|e| e.fragment.is_some() && e.ty != EntryType::Section && e.name.starts_with("fixture::"))
+            .filter(|e| !matches!(e.ty, EntryType::Impl | EntryType::Implementor) && e.name != "fixture::MyStruct::into")
+            .collect::<Vec<_>>();
+        for entry in &html_members {
+            assert!(members.contains(entry), "Expected entry {:?} in {:?}", entry, members);
+        }
+        let only_json = members.iter().filter(|e| !html_members.contains(e)).map(|e| e.index_path()).collect::<Vec<_>>();
+        assert_eq!(
+            only_json,
+            ["fixture/struct.MyStruct.html#method.old", "fixture/struct.MyStruct.html#method.provided"]
+        );
+        // Aliases point at the aliased item.
+        let alias = entries.iter().find(|e| e.name == "my_alias (→ fixture::MyStruct)").unwrap();
+        assert_eq!(alias.index_path(), "fixture/struct.MyStruct.html");
+        let alias = entries.iter().find(|e| e.name == "create (→ fixture::MyStruct::new)").unwrap();
+        assert_eq!(alias.index_path(), "fixture/struct.MyStruct.html#method.new");
+        // Blanket impls are not indexed.
+        assert!(!entries.iter().any(|e| e.name == "fixture::MyStruct::into"));
+    }
+
+    #[test]
+    fn test_doc_aliases() {
+        let krate = super::read_crate(&fixture_json_dir().join("fixture.json")).unwrap();
+        let mut item = krate.index.values().find(|i| i.name.as_deref() == Some("MyStruct")).unwrap().clone();
+        assert_eq!(doc_aliases(&item), vec!["my_alias".to_owned()]);
+        item.attrs = vec![rustdoc_types::Attribute::Other("#[doc(alias(\"a\", \"b\"))]".to_owned())];
+        assert_eq!(doc_aliases(&item), vec!["a".to_owned(), "b".to_owned()]);
+    }
+}
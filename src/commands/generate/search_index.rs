@@ -0,0 +1,435 @@
+//! Entry source reading the search index generated by rustdoc (`search-index.js`), instead of
+//! inferring the items from the file names.
+//!
+//! The format of the search index is an implementation detail of rustdoc and changes regularly.
+//! This module understands the variants used between Rust 1.70 and the switch to the
+//! `search.index/` directory.
+
+use crate::error::*;
+
+use super::{alias_entry, DocsetEntry, EntryType};
+
+use serde_json::{Map, Value};
+use snafu::{OptionExt, ResultExt};
+
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf}
+};
+
+/// Name of the search index file in the rustdoc output directory.
+const SEARCH_INDEX_FILE: &str = "search-index.js";
+
+/// Item types, in the order of rustdoc's `ItemType` enum which is used to encode them in the
+/// search index.
+const ITEM_TYPES: &[&str] = &[
+    "keyword",
+    "primitive",
+    "mod",
+    "externcrate",
+    "import",
+    "struct",
+    "enum",
+    "fn",
+    "type",
+    "static",
+    "trait",
+    "impl",
+    "tymethod",
+    "method",
+    "structfield",
+    "variant",
+    "macro",
+    "associatedtype",
+    "constant",
+    "associatedconstant",
+    "union",
+    "foreigntype",
+    "opaque",
+    "attr",
+    "derive",
+    "traitalias",
+    "generic"
+];
+
+/// An item of the search index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchIndexItem {
+    pub crate_name: String,
+    pub ty: &'static str,
+    pub name: String,
+    /// Path of the module containing the item, or containing its parent.
+    pub path: String,
+    /// Type and name of the item this one is a member of, e.g. the struct of a method.
+    pub parent: Option<(&'static str, String)>,
+    /// Aliases declared with `#[doc(alias = "...")]`.
+    pub aliases: Vec<String>
+}
+
+impl SearchIndexItem {
+    /// Return the path of the page documenting this item relative to the rustdoc root, and the
+    /// anchor of the item inside that page if it does not have its own page.
+    pub fn page(&self) -> (PathBuf, Option<String>) {
+        let mut page = PathBuf::new();
+        page.extend(self.path.split("::"));
+        match (&self.parent, self.ty) {
+            (Some((parent_ty, parent_name)), ty) => {
+                page.push(format!("{}.{}.html", parent_ty, parent_name));
+                (page, Some(format!("{}.{}", ty, self.name)))
+            }
+            (None, "mod") => {
+                page.push(&self.name);
+                page.push("index.html");
+                (page, None)
+            }
+            (None, ty) => {
+                page.push(format!("{}.{}.html", ty, self.name));
+                (page, None)
+            }
+        }
+    }
+
+    /// Return the fully qualified name of the item.
+    pub fn qualified_name(&self) -> String {
+        match &self.parent {
+            Some((_, parent_name)) => format!("{}::{}::{}", self.path, parent_name, self.name),
+            None => format!("{}::{}", self.path, self.name)
+        }
+    }
+
+    /// Return the docset entry for this item, if it is an item we index.
+    pub fn docset_entry(&self) -> Option<DocsetEntry> {
+        let ty = match &self.parent {
+            Some((parent_ty, _)) => {
+                // Members are only indexed when their parent has a page of its own, which excludes
+                // e.g. the fields of enum variants.
+                if !EntryType::from_rustdoc_prefix(parent_ty).is_some_and(|ty| ty.has_members()) {
+                    return None;
+                }
+                EntryType::from_rustdoc_member_prefix(self.ty)?
+            }
+            None if self.ty == "mod" => EntryType::Module,
+            None => EntryType::from_rustdoc_prefix(self.ty)?
+        };
+        let (path, fragment) = self.page();
+        Some(DocsetEntry::new(self.qualified_name(), ty, path, fragment))
+    }
+}
+
+/// Decode a string of integers in rustdoc's "VLQ hex" encoding.
+fn decode_vlq_hex(s: &str) -> Option<Vec<i64>> {
+    let mut values = vec![];
+    let mut value: i64 = 0;
+    for b in s.bytes() {
+        match b {
+            // Continuation hexits.
+            b'@'..=b'O' => value = (value << 4) | i64::from(b - b'@'),
+            // Last hexit of a value, which is zig-zag encoded.
+            b'`'..=b'o' => {
+                value = (value << 4) | i64::from(b - b'`');
+                values.push(if value & 1 == 1 { -(value >> 1) } else { value >> 1 });
+                value = 0;
+            }
+            _ => return None
+        }
+    }
+    Some(values)
+}
+
+/// Unescape the contents of the single-quoted JavaScript string literal the index is stored in.
+fn unescape_js_string(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // Line continuation
+            Some('\n') | None => (),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c)
+        }
+    }
+    unescaped
+}
+
+/// Extract the JSON document embedded in the `search-index.js` file.
+fn extract_json(js: &str) -> Result<Value> {
+    let start = js.find("JSON.parse('").context(SearchIndexSnafu {
+        msg: "could not find the index data"
+    })? + "JSON.parse('".len();
+    let len = js[start..].find("')").context(SearchIndexSnafu {
+        msg: "could not find the end of the index data"
+    })?;
+    serde_json::from_str(&unescape_js_string(&js[start..start + len])).context(SearchIndexJsonSnafu)
+}
+
+fn item_type(ty: i64) -> Option<&'static str> {
+    usize::try_from(ty).ok().and_then(|ty| ITEM_TYPES.get(ty)).copied()
+}
+
+/// Decode the items of a single crate of the search index.
+fn parse_crate(crate_name: &str, data: &Map<String, Value>) -> Result<Vec<SearchIndexItem>> {
+    let invalid = |msg: &'static str| SearchIndexSnafu { msg }.build();
+
+    let types = data.get("t").and_then(Value::as_str).ok_or_else(|| invalid("missing item types"))?;
+    let types = types
+        .bytes()
+        .map(|b| b.checked_sub(b'A').and_then(|ty| item_type(ty.into())))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| invalid("invalid item type"))?;
+    let names = data
+        .get("n")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("missing item names"))?
+        .iter()
+        .map(|n| n.as_str().unwrap_or_default().to_owned())
+        .collect::<Vec<_>>();
+
+    // Item paths are only stored when they differ from the path of the previous item, either as an
+    // array of `[item index, path]` pairs or as an array with an empty string for unchanged paths.
+    let mut paths = vec![None; names.len()];
+    for (i, path) in data.get("q").and_then(Value::as_array).into_iter().flatten().enumerate() {
+        let (i, path) = match path {
+            Value::Array(pair) => (pair.first().and_then(Value::as_u64).map(|i| i as usize), pair.get(1)),
+            path => (Some(i), Some(path))
+        };
+        match (i.and_then(|i| paths.get_mut(i)), path.and_then(Value::as_str)) {
+            (Some(slot), Some(path)) if !path.is_empty() => *slot = Some(path.to_owned()),
+            _ => ()
+        }
+    }
+
+    let parents = data
+        .get("p")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|p| {
+            let ty = p.get(0).and_then(Value::as_i64).and_then(item_type)?;
+            Some((ty, p.get(1)?.as_str()?.to_owned()))
+        })
+        .collect::<Vec<_>>();
+    let parent_indices = match data.get("i") {
+        Some(Value::String(s)) => decode_vlq_hex(s).ok_or_else(|| invalid("invalid parent indices"))?,
+        Some(Value::Array(a)) => a.iter().map(|i| i.as_i64().unwrap_or_default()).collect(),
+        _ => vec![]
+    };
+
+    let mut items = vec![];
+    let mut last_path = crate_name.to_owned();
+    for (i, (ty, name)) in types.into_iter().zip(names).enumerate() {
+        if let Some(path) = &paths[i] {
+            last_path = path.clone();
+        }
+        // Parent indices are 1-based, 0 meaning the item has no parent.
+        let parent = match parent_indices.get(i) {
+            Some(&p) if p > 0 => parents.get(p as usize - 1).cloned().flatten(),
+            _ => None
+        };
+        items.push(SearchIndexItem {
+            crate_name: crate_name.to_owned(),
+            ty,
+            name,
+            path: last_path.clone(),
+            parent,
+            aliases: vec![]
+        });
+    }
+
+    if let Some(aliases) = data.get("a").and_then(Value::as_object) {
+        for (alias, indices) in aliases {
+            for i in indices.as_array().into_iter().flatten().filter_map(Value::as_u64) {
+                if let Some(item) = items.get_mut(i as usize) {
+                    item.aliases.push(alias.to_owned());
+                }
+            }
+        }
+    }
+
+    Ok(items)
+}
+
+/// Read the items of every crate in the search index of a rustdoc output directory. Return
+/// `None` if the directory has no search index in a format we understand.
+pub fn read_search_index(rustdoc_root_dir: &Path) -> Result<Option<Vec<SearchIndexItem>>> {
+    let index_path = rustdoc_root_dir.join(SEARCH_INDEX_FILE);
+    if !index_path.is_file() {
+        return Ok(None);
+    }
+    let json = extract_json(&read_to_string(index_path).context(IoReadSnafu)?)?;
+
+    // The index is either an object or an array of `[crate, data]` pairs depending on the rustdoc
+    // version.
+    let crates = match json {
+        Value::Object(crates) => crates.into_iter().collect::<Vec<_>>(),
+        Value::Array(crates) => crates
+            .into_iter()
+            .filter_map(|c| match c {
+                Value::Array(mut pair) if pair.len() == 2 => {
+                    let data = pair.pop()?;
+                    Some((pair.pop()?.as_str()?.to_owned(), data))
+                }
+                _ => None
+            })
+            .collect(),
+        _ => return SearchIndexSnafu { msg: "unexpected index structure" }.fail()
+    };
+
+    let mut items = vec![];
+    for (crate_name, data) in crates {
+        let data = data.as_object().context(SearchIndexSnafu {
+            msg: "unexpected crate data structure"
+        })?;
+        items.extend(parse_crate(&crate_name, data)?);
+    }
+    Ok(Some(items))
+}
+
+/// Generate the docset entries from the search index of a rustdoc output directory. Return
+/// `None` if the directory has no search index in a format we understand.
+pub fn search_index_entries(rustdoc_root_dir: &Path) -> Result<Option<Vec<DocsetEntry>>> {
+    let items = match read_search_index(rustdoc_root_dir)? {
+        Some(items) => items,
+        None => return Ok(None)
+    };
+
+    let mut entries = vec![];
+    let mut crates: Vec<&str> = vec![];
+    for item in &items {
+        // Crates are not items of their own in the search index.
+        if !crates.contains(&item.crate_name.as_str()) {
+            crates.push(&item.crate_name);
+            entries.push(DocsetEntry::new(
+                item.crate_name.clone(),
+                EntryType::Package,
+                Path::new(&item.crate_name).join("index.html"),
+                None
+            ));
+        }
+        if let Some(entry) = item.docset_entry() {
+            entries.extend(item.aliases.iter().map(|alias| alias_entry(alias, &entry)));
+            entries.push(entry);
+        }
+    }
+    Ok(Some(entries))
+}
+
+/// Generate the entries for the `#[doc(alias = "...")]` aliases recorded in the search index of a
+/// rustdoc output directory, if it has one.
+pub fn alias_entries(rustdoc_root_dir: &Path) -> Result<Vec<DocsetEntry>> {
+    let items = read_search_index(rustdoc_root_dir)?.unwrap_or_default();
+    Ok(items
+        .iter()
+        .filter(|item| !item.aliases.is_empty())
+        .filter_map(|item| Some((item, item.docset_entry()?)))
+        .flat_map(|(item, entry)| item.aliases.iter().map(move |alias| alias_entry(alias, &entry)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        alias_entries, decode_vlq_hex, extract_json, read_search_index, search_index_entries, unescape_js_string
+    };
+    use crate::{
+        commands::generate::{
+            tests::{fixture_rustdoc_dir, read_fixture},
+            walk_rustdoc_dir, DocsetEntry, EntryType
+        },
+        Reexports
+    };
+
+    /// Return the index data of each crate of the fixture search index.
+    fn fixture_crates() -> Vec<(String, serde_json::Value)> {
+        serde_json::from_value(extract_json(&read_fixture("search-index.js")).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_extract_json() {
+        // The index data is a JavaScript string with line continuations and escaped quotes.
+        let crates = fixture_crates();
+        assert_eq!(crates.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), ["fixture", "std"]);
+        assert_eq!(unescape_js_string("[\\\n\"it\\'s\"\\\n]"), "[\"it's\"]");
+    }
+
+    #[test]
+    fn test_decode_vlq_hex() {
+        // The parents of the items are encoded with one value per item.
+        for (name, data) in fixture_crates() {
+            let item_count = data["t"].as_str().unwrap().len();
+            let parents = decode_vlq_hex(data["i"].as_str().unwrap());
+            assert_eq!(parents.map(|p| p.len()), Some(item_count), "Unexpected parents of {}", name);
+        }
+        assert_eq!(decode_vlq_hex("`bdA`Aa").unwrap(), vec![0, 1, 2, 8, -8]);
+        assert!(decode_vlq_hex("z").is_none());
+    }
+
+    #[test]
+    fn test_read_search_index_parents() {
+        let items = read_search_index(&fixture_rustdoc_dir()).unwrap().unwrap();
+        let new = items.iter().find(|i| i.name == "new").unwrap();
+        assert_eq!(new.parent, Some(("struct", "MyStruct".to_owned())));
+        assert_eq!(new.path, "fixture");
+        let is_ascii = items.iter().find(|i| i.name == "is_ascii").unwrap();
+        assert_eq!(is_ascii.parent, Some(("primitive", "u8".to_owned())));
+        assert_eq!(is_ascii.crate_name, "std");
+    }
+
+    #[test]
+    fn test_search_index_entries_members() {
+        // The members found in the search index are the ones found in the pages.
+        let entries = search_index_entries(&fixture_rustdoc_dir()).unwrap().unwrap();
+        let from_filenames = walk_rustdoc_dir(&fixture_rustdoc_dir(), Reexports::Canonical).unwrap();
+        let members = entries
+            .iter()
+            .filter(|e| e.fragment.is_some() && !e.name.contains(" (→ "))
+            .collect::<Vec<_>>();
+        for entry in &members {
+            assert!(from_filenames.contains(entry), "Unexpected entry {:?}", entry);
+        }
+        assert!(members.iter().any(|e| e.ty == EntryType::Variant));
+        assert!(members.iter().any(|e| e.name == "std::u8::MAX"));
+        // Fields of enum variants don't have a page of their own.
+        assert!(!entries.iter().any(|e| e.name.ends_with("::value")));
+    }
+
+    #[test]
+    fn test_search_index_and_filenames_agree_on_pages() {
+        let root = &fixture_rustdoc_dir();
+        let sort = |mut entries: Vec<DocsetEntry>| {
+            entries.retain(|e| e.fragment.is_none() && !e.name.contains(" (→ "));
+            entries.sort_by(|a, b| a.path.cmp(&b.path));
+            entries
+        };
+        let from_index = sort(search_index_entries(root).unwrap().unwrap());
+        let from_filenames = sort(walk_rustdoc_dir(root, Reexports::Canonical).unwrap());
+        assert_eq!(from_index, from_filenames);
+    }
+
+    #[test]
+    fn test_search_index_aliases() {
+        let expected = &[
+            ("my_alias (→ fixture::MyStruct)", "fixture/struct.MyStruct.html"),
+            ("create (→ fixture::MyStruct::new)", "fixture/struct.MyStruct.html#method.new")
+        ];
+        let entries = search_index_entries(&fixture_rustdoc_dir()).unwrap().unwrap();
+        let aliases = alias_entries(&fixture_rustdoc_dir()).unwrap();
+        assert_eq!(aliases.len(), expected.len());
+        for (name, path) in expected {
+            for entries in [&entries, &aliases] {
+                let entry = entries.iter().find(|e| &e.name == name);
+                assert!(entry.is_some(), "Expected entry {} in {:?}", name, entries);
+                assert_eq!(&entry.unwrap().index_path(), path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_index_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(search_index_entries(dir.path()).unwrap().is_none());
+    }
+}
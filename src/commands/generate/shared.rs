@@ -0,0 +1,206 @@
+//! Filtering of the scripts rustdoc shares between the crates of a documentation directory, such
+//! as the list of crates, the search index or the implementors of traits, so that they only
+//! mention the crates of a docset.
+//!
+//! Recent rustdoc versions end these scripts with a comment giving the offset of the crate parts
+//! and their lengths, e.g. `//{"start":21,"fragment_lengths":[6,12]}`. Older versions write the
+//! part of each crate on its own line.
+
+use serde::Deserialize;
+
+use std::{ffi::OsStr, path::Path};
+
+/// Directories in which rustdoc stores the implementors of traits and the implementations of
+/// types, which are defined in other crates.
+const SHARED_DIRS: &[&str] = &["implementors", "trait.impl", "type.impl"];
+
+/// Prefixes of the shared scripts stored in the root of the rustdoc directory. Their names may be
+/// suffixed with the rustdoc version.
+const SHARED_ROOT_SCRIPTS: &[&str] = &["crates", "search-index", "src-files", "source-files"];
+
+/// The comment ending the shared scripts of recent rustdoc versions.
+#[derive(Debug, Deserialize)]
+struct Fragments {
+    start: usize,
+    fragment_lengths: Vec<usize>
+}
+
+/// Whether a file of a rustdoc directory, given by its path relative to it, is a script shared by
+/// the crates of the directory.
+pub fn is_shared_script(path: &Path) -> bool {
+    if path.extension() != Some(OsStr::new("js")) {
+        return false;
+    }
+    let mut components = path.iter();
+    match (components.next(), components.next()) {
+        (Some(name), None) => SHARED_ROOT_SCRIPTS.iter().any(|prefix| name.to_string_lossy().starts_with(prefix)),
+        (Some(dir), Some(_)) => SHARED_DIRS.iter().any(|shared_dir| dir == *shared_dir),
+        _ => false
+    }
+}
+
+/// Return the name of the crate a part of a shared script belongs to, which is the first string
+/// literal of the part, e.g. `["my_crate",{...}]` or `implementors["my_crate"] = [...];`.
+fn crate_name(part: &str) -> Option<&str> {
+    let part = part.trim_start().trim_start_matches(',');
+    let part = part.strip_prefix("implementors[").unwrap_or(part).trim_start_matches('[');
+    let (name, rest) = part.strip_prefix('"')?.split_once('"')?;
+    let is_crate_name = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    (is_crate_name && (rest.is_empty() || rest.starts_with([',', ':', ']']))).then_some(name)
+}
+
+/// Filter the crate parts of a script ending with their offset and lengths.
+fn filter_fragments(js: &str, keep: &dyn Fn(&str) -> bool) -> Option<String> {
+    let comment_start = js.trim_end().rfind('\n')? + 1;
+    let fragments = serde_json::from_str::<Fragments>(js[comment_start..].trim_end().strip_prefix("//")?).ok()?;
+
+    let mut parts = vec![];
+    let mut end = fragments.start;
+    for len in fragments.fragment_lengths {
+        let part = js.get(end..end + len)?;
+        // The parts are separated by commas, which belong to the part that follows them.
+        parts.push(part.strip_prefix(',').unwrap_or(part));
+        end += len;
+    }
+    let kept = parts.into_iter().filter(|part| crate_name(part).is_none_or(keep)).collect::<Vec<_>>();
+    let lengths = kept
+        .iter()
+        .enumerate()
+        .map(|(i, part)| (part.len() + usize::from(i > 0)).to_string())
+        .collect::<Vec<_>>();
+    Some(format!(
+        "{}{}{}//{{\"start\":{},\"fragment_lengths\":[{}]}}{}",
+        &js[..fragments.start],
+        kept.join(","),
+        js.get(end..comment_start)?,
+        fragments.start,
+        lengths.join(","),
+        &js[js.trim_end().len()..]
+    ))
+}
+
+/// Filter the list of crates of older rustdoc versions, e.g. `window.ALL_CRATES = ["my_crate"];`.
+fn filter_crate_list(js: &str, keep: &dyn Fn(&str) -> bool) -> Option<String> {
+    let list_start = js.find("ALL_CRATES = [")? + "ALL_CRATES = ".len();
+    let list_len = js[list_start..].find(']')? + 1;
+    let crates = serde_json::from_str::<Vec<String>>(&js[list_start..list_start + list_len]).ok()?;
+    let crates = crates.into_iter().filter(|name| keep(name)).collect::<Vec<_>>();
+    Some(format!(
+        "{}{}{}",
+        &js[..list_start],
+        serde_json::to_string(&crates).ok()?,
+        &js[list_start + list_len..]
+    ))
+}
+
+/// Filter the crate lines of a script of older rustdoc versions. Consecutive crate lines may be
+/// the elements of a JSON array or object, whose separating commas are fixed up.
+fn filter_lines(js: &str, keep: &dyn Fn(&str) -> bool) -> String {
+    // Split a line into its contents, without the separating comma, and its end.
+    fn split_line(line: &str) -> (&str, bool, &str) {
+        let contents = line.trim_end_matches(['\n', '\r', '\\']);
+        let end = &line[contents.len()..];
+        match contents.strip_suffix(',') {
+            Some(contents) => (contents, true, end),
+            None => (contents, false, end)
+        }
+    }
+
+    let lines = js.split_inclusive('\n').collect::<Vec<_>>();
+    let mut filtered = String::with_capacity(js.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let run_len = lines[i..].iter().take_while(|line| crate_name(line).is_some()).count();
+        if run_len == 0 {
+            filtered.push_str(lines[i]);
+            i += 1;
+            continue;
+        }
+        let run = &lines[i..i + run_len];
+        let comma_separated = run.len() > 1 && run[..run.len() - 1].iter().all(|line| split_line(line).1);
+        let last_comma = split_line(run[run.len() - 1]).1;
+        let kept = run.iter().filter(|line| crate_name(line).is_some_and(keep)).collect::<Vec<_>>();
+        for (j, line) in kept.iter().enumerate() {
+            if comma_separated {
+                let (contents, _, end) = split_line(line);
+                let comma = if j + 1 < kept.len() || last_comma { "," } else { "" };
+                filtered.push_str(&format!("{}{}{}", contents, comma, end));
+            } else {
+                filtered.push_str(line);
+            }
+        }
+        i += run_len;
+    }
+    filtered
+}
+
+/// Remove the parts of the crates which are not kept from a file of a rustdoc directory, given by
+/// its path relative to it, if it is a shared script. Other files are returned unchanged.
+pub fn filter_crates(path: &Path, js: &str, keep: &dyn Fn(&str) -> bool) -> String {
+    if !is_shared_script(path) {
+        return js.to_owned();
+    }
+    filter_fragments(js, keep)
+        .or_else(|| filter_crate_list(js, keep))
+        .unwrap_or_else(|| filter_lines(js, keep))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{filter_crates, is_shared_script};
+    use crate::commands::generate::tests::read_fixture;
+
+    #[test]
+    fn test_is_shared_script() {
+        assert!(is_shared_script(Path::new("crates.js")));
+        assert!(is_shared_script(Path::new("search-index1.70.0.js")));
+        assert!(is_shared_script(Path::new("trait.impl/core/clone/trait.Clone.js")));
+        assert!(is_shared_script(Path::new("implementors/fixture/trait.MyTrait.js")));
+        assert!(!is_shared_script(Path::new("fixture/sidebar-items.js")));
+        assert!(!is_shared_script(Path::new("static.files/main.js")));
+        assert!(!is_shared_script(Path::new("fixture/index.html")));
+    }
+
+    #[test]
+    fn test_filter_fragments() {
+        let path = Path::new("crates.js");
+        let crates = read_fixture("crates.js");
+        assert_eq!(
+            filter_crates(path, &crates, &|name| name != "std"),
+            "window.ALL_CRATES = [\"fixture\"];\n//{\"start\":21,\"fragment_lengths\":[9]}\n"
+        );
+        assert_eq!(
+            filter_crates(path, &crates, &|name| name != "fixture"),
+            "window.ALL_CRATES = [\"std\"];\n//{\"start\":21,\"fragment_lengths\":[5]}\n"
+        );
+        assert_eq!(filter_crates(path, &crates, &|_| true), crates);
+    }
+
+    #[test]
+    fn test_filter_crate_list() {
+        let crates = "window.ALL_CRATES = [\"fixture\",\"std\"];";
+        assert_eq!(filter_crates(Path::new("crates.js"), crates, &|name| name == "std"), "window.ALL_CRATES = [\"std\"];");
+    }
+
+    #[test]
+    fn test_filter_lines() {
+        let path = Path::new("search-index.js");
+        let search_index = read_fixture("search-index.js");
+        // The comma after the last element of the search index is removed.
+        let filtered = filter_crates(path, &search_index, &|name| name == "fixture");
+        assert!(filtered.contains("[\"fixture\","));
+        assert!(!filtered.contains("[\"std\","));
+        assert!(filtered.contains("}]\\\n]'));"), "Unexpected search index {}", filtered);
+        let filtered = filter_crates(path, &search_index, &|name| name == "std");
+        assert!(!filtered.contains("[\"fixture\","));
+        assert_eq!(filtered.lines().count(), search_index.lines().count() - 1);
+        assert_eq!(filter_crates(path, &search_index, &|_| true), search_index);
+
+        let path = Path::new("implementors/fixture/trait.MyTrait.js");
+        let implementors = read_fixture("implementors/fixture/trait.MyTrait.js");
+        assert!(!filter_crates(path, &implementors, &|name| name != "other").contains("Other"));
+        assert_eq!(filter_crates(path, &implementors, &|_| true), implementors);
+    }
+}
@@ -0,0 +1,202 @@
+//! Storage of the documentation of a docset in a compressed, indexed tarball, which Dash reads
+//! without extracting it.
+//!
+//! The `Documents` directory is stored in `Contents/Resources/tarix.tgz`. The deflate stream is
+//! fully flushed before each entry, so that decompression can start at the entry's header without
+//! reading the preceding data. `Contents/Resources/tarixIndex.db` maps the path of each file to
+//! its location in the tarball, as `<tar block> <compressed offset> <size>`: the number of the
+//! 512 bytes tar block of the entry's header, the offset in `tarix.tgz` from which decompression
+//! can start at this header, and the size of the file.
+
+use crate::error::*;
+
+use super::archive::append_dir;
+
+use flate2::{Compress, Compression, Crc, FlushCompress, Status};
+use rusqlite::{params, Connection};
+use snafu::ResultExt;
+use tar::Builder;
+
+use std::{
+    fs::{remove_dir_all, File},
+    io::{self, BufWriter, Write},
+    path::Path
+};
+
+/// Size of the header of a gzip stream without optional fields.
+const GZIP_HEADER_LEN: u64 = 10;
+
+/// A gzip encoder which can fully flush its deflate stream, which the flate2 writers can't.
+struct TarixWriter<W: Write> {
+    output: W,
+    compress: Compress,
+    crc: Crc,
+    buffer: Vec<u8>
+}
+
+impl<W: Write> TarixWriter<W> {
+    fn new(mut output: W) -> io::Result<TarixWriter<W>> {
+        // Magic, deflate method, no flags, null mtime, no extra flags, unknown OS.
+        output.write_all(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255])?;
+        Ok(TarixWriter {
+            output,
+            compress: Compress::new(Compression::best(), false),
+            crc: Crc::new(),
+            buffer: vec![0; 64 * 1024]
+        })
+    }
+
+    fn deflate(&mut self, mut input: &[u8], flush: FlushCompress) -> io::Result<()> {
+        loop {
+            let (total_in, total_out) = (self.compress.total_in(), self.compress.total_out());
+            let status = self
+                .compress
+                .compress(input, &mut self.buffer, flush)
+                .map_err(io::Error::other)?;
+            let consumed = (self.compress.total_in() - total_in) as usize;
+            let produced = (self.compress.total_out() - total_out) as usize;
+            self.output.write_all(&self.buffer[..produced])?;
+            input = &input[consumed..];
+            // The output buffer being full means there may be more output pending.
+            let done = input.is_empty() && (flush == FlushCompress::None || produced < self.buffer.len());
+            if status == Status::StreamEnd || (done && flush != FlushCompress::Finish) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Fully flush the deflate stream, and return the offset in the output at which decompression
+    /// can start again.
+    fn restart_point(&mut self) -> io::Result<u64> {
+        self.deflate(&[], FlushCompress::Full)?;
+        Ok(GZIP_HEADER_LEN + self.compress.total_out())
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.deflate(&[], FlushCompress::Finish)?;
+        self.output.write_all(&self.crc.sum().to_le_bytes())?;
+        self.output.write_all(&self.crc.amount().to_le_bytes())?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+impl<W: Write> Write for TarixWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.crc.update(buf);
+        self.deflate(buf, FlushCompress::None)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Write the index of the files of the tarball.
+fn write_index(index_path: &Path, index: Vec<(String, String)>) -> Result<()> {
+    let mut conn = Connection::open(index_path).context(SqliteSnafu)?;
+    // Dash extracts the files listed in `toextract` out of the tarball when installing the
+    // docset. It reads all the documentation from the tarball, so the table is left empty.
+    conn.execute_batch(
+        "CREATE TABLE tarindex(path TEXT PRIMARY KEY COLLATE NOCASE, hash TEXT);
+        CREATE TABLE toextract(path TEXT PRIMARY KEY COLLATE NOCASE, hash TEXT);"
+    )
+    .context(SqliteSnafu)?;
+    let transaction = conn.transaction().context(SqliteSnafu)?;
+    {
+        let mut stmt = transaction
+            .prepare("INSERT OR IGNORE INTO tarindex (path, hash) VALUES (?1, ?2)")
+            .context(SqliteSnafu)?;
+        for (path, hash) in index {
+            stmt.execute(params![path, hash]).context(SqliteSnafu)?;
+        }
+    }
+    transaction.commit().context(SqliteSnafu)
+}
+
+/// Move the `Documents` directory of a docset into `tarix.tgz`, and write its index, in the given
+/// `Contents/Resources` directory of the docset.
+pub fn pack_documents(resources_dir: &Path) -> Result<()> {
+    let documents_dir = resources_dir.join("Documents");
+    let file = File::create(resources_dir.join("tarix.tgz")).context(IoWriteSnafu)?;
+    let mut builder = Builder::new(TarixWriter::new(BufWriter::new(file)).context(IoWriteSnafu)?);
+
+    // Record the location of each file before appending it, as the deflate stream is flushed so
+    // that decompression can start at its header.
+    let mut index = Vec::new();
+    append_dir(&mut builder, &documents_dir, Path::new("Documents"), &mut |builder, archive_path, size| {
+        let offset = builder.get_mut().restart_point().context(IoWriteSnafu)?;
+        let block = builder.get_mut().compress.total_in() / 512;
+        let archive_path = archive_path.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/");
+        index.push((archive_path, format!("{} {} {}", block, offset, size)));
+        Ok(())
+    })?;
+    builder
+        .into_inner()
+        .and_then(TarixWriter::finish)
+        .context(IoWriteSnafu)?;
+
+    write_index(&resources_dir.join("tarixIndex.db"), index)?;
+    remove_dir_all(documents_dir).context(IoWriteSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{read, File},
+        io::Read
+    };
+
+    use flate2::{read::GzDecoder, Decompress, FlushDecompress};
+    use rusqlite::Connection;
+    use tar::Archive;
+
+    use super::pack_documents;
+    use crate::commands::generate::tests::{copy_fixture, fixture_rustdoc_dir, relative_files};
+
+    #[test]
+    fn test_pack_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let resources_dir = dir.path().join("Contents/Resources");
+        let documents_dir = resources_dir.join("Documents");
+        copy_fixture(&documents_dir);
+        let files = relative_files(&documents_dir);
+
+        pack_documents(&resources_dir).unwrap();
+        assert!(!documents_dir.exists());
+
+        // The tarball is a regular gzip-compressed tarball.
+        let tarball = read(resources_dir.join("tarix.tgz")).unwrap();
+        let unpacked = tempfile::tempdir().unwrap();
+        Archive::new(GzDecoder::new(File::open(resources_dir.join("tarix.tgz")).unwrap()))
+            .unpack(unpacked.path())
+            .unwrap();
+        assert_eq!(relative_files(&unpacked.path().join("Documents")), files);
+        let mut tar = Vec::new();
+        GzDecoder::new(&tarball[..]).read_to_end(&mut tar).unwrap();
+
+        // Every file can be read from its location in the index.
+        let conn = Connection::open(resources_dir.join("tarixIndex.db")).unwrap();
+        let indexed: usize = conn.query_row("SELECT COUNT(*) FROM tarindex", [], |row| row.get(0)).unwrap();
+        assert_eq!(indexed, files.len());
+        for file in files {
+            let contents = read(fixture_rustdoc_dir().join(&file)).unwrap();
+            let path = format!("Documents/{}", file.to_string_lossy());
+            let hash: String = conn
+                .query_row("SELECT hash FROM tarindex WHERE path = ?1", [&path], |row| row.get(0))
+                .unwrap();
+            let location = hash.split(' ').map(|n| n.parse::<usize>().unwrap()).collect::<Vec<_>>();
+            let (block, offset, size) = (location[0], location[1], location[2]);
+            assert_eq!(size, contents.len(), "Unexpected size of {}", path);
+
+            let mut decompressed = Vec::with_capacity(512 + size);
+            Decompress::new(false)
+                .decompress_vec(&tarball[offset..], &mut decompressed, FlushDecompress::None)
+                .unwrap();
+            assert_eq!(&decompressed[512..512 + size], &contents[..], "Unexpected contents of {}", path);
+            // The block is the position of the header in the tarball.
+            assert_eq!(&tar[block * 512..block * 512 + 512], &decompressed[..512]);
+        }
+    }
+}
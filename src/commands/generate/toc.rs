@@ -0,0 +1,96 @@
+//! Insertion of the anchors Dash and Zeal use to build the table of contents of a page.
+//!
+//! See <https://kapeli.com/docsets#tableofcontents> for the details.
+
+use super::{
+    html::{element_ids, element_text, percent_decode, percent_encode, strip_disambiguator},
+    parse_member_anchor, EntryType
+};
+
+/// Return the Dash entry type and name of the table of contents entry for the element with the
+/// given tag name and id, if it should have one.
+fn toc_entry(html: &str, tag_start: usize, tag_name: &str, id: &str) -> Option<(String, String)> {
+    if let Some((ty, name)) = parse_member_anchor(id) {
+        return Some((ty.to_string(), name.to_owned()));
+    }
+    if let Some(impl_id) = id.strip_prefix("impl-") {
+        // Prefer the impl header as rendered by rustdoc, and reconstruct it from the anchor
+        // otherwise.
+        let name = element_text(html, tag_start, tag_name)
+            .filter(|text| text.starts_with("impl"))
+            .unwrap_or_else(|| {
+                format!("impl {}", percent_decode(strip_disambiguator(impl_id)).replace("-for-", " for "))
+            });
+        return Some((EntryType::Section.to_string(), name));
+    }
+    if matches!(tag_name, "h2" | "h3" | "h4" | "h5" | "h6") {
+        let name = element_text(html, tag_start, tag_name)?;
+        if !name.is_empty() {
+            return Some((EntryType::Section.to_string(), name));
+        }
+    }
+    None
+}
+
+/// Insert a `dashAnchor` before every method, field, variant, associated item, impl block and
+/// section heading of a rustdoc HTML page.
+pub fn insert_dash_anchors(html: &str) -> String {
+    let mut output = String::with_capacity(html.len() + html.len() / 8);
+    let mut copied = 0;
+    for (tag_start, tag_name, id) in element_ids(html) {
+        if tag_start < copied {
+            continue;
+        }
+        if let Some((ty, name)) = toc_entry(html, tag_start, tag_name, id) {
+            output.push_str(&html[copied..tag_start]);
+            output.push_str(&format!(
+                "<a name=\"//apple_ref/cpp/{}/{}\" class=\"dashAnchor\"></a>",
+                ty,
+                percent_encode(&name)
+            ));
+            copied = tag_start;
+        }
+    }
+    output.push_str(&html[copied..]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::insert_dash_anchors;
+    use crate::commands::generate::tests::read_fixture;
+
+    #[test]
+    fn test_insert_dash_anchors_struct_page() {
+        let html = insert_dash_anchors(&read_fixture("fixture/struct.MyStruct.html"));
+
+        let expected = &[
+            "<a name=\"//apple_ref/cpp/Section/Fields\" class=\"dashAnchor\"></a><h2 id=\"fields\"",
+            "<a name=\"//apple_ref/cpp/Field/field\" class=\"dashAnchor\"></a><span id=\"structfield.field\"",
+            "<a name=\"//apple_ref/cpp/Section/impl%20MyStruct\" class=\"dashAnchor\"></a><section id=\"impl-MyStruct\"",
+            "<a name=\"//apple_ref/cpp/Method/new\" class=\"dashAnchor\"></a><section id=\"method.new\"",
+            "<a name=\"//apple_ref/cpp/Constant/ZERO\" class=\"dashAnchor\"></a><section id=\"associatedconstant.ZERO\"",
+            "<a name=\"//apple_ref/cpp/Section/impl%20MyTrait%20for%20MyStruct\" class=\"dashAnchor\"></a>",
+            "<a name=\"//apple_ref/cpp/Type/Output\" class=\"dashAnchor\"></a><section id=\"associatedtype.Output\"",
+            "<a name=\"//apple_ref/cpp/Section/Trait%20Implementations\" class=\"dashAnchor\"></a>"
+        ];
+        for anchor in expected {
+            assert!(html.contains(anchor), "Expected {} in {}", anchor, html);
+        }
+    }
+
+    #[test]
+    fn test_insert_dash_anchors_enum_page() {
+        let html = insert_dash_anchors(&read_fixture("fixture/enum.MyEnum.html"));
+
+        assert!(html.contains("<a name=\"//apple_ref/cpp/Variant/First\" class=\"dashAnchor\"></a><section id=\"variant.First\""));
+        assert!(html.contains("<a name=\"//apple_ref/cpp/Variant/Second\" class=\"dashAnchor\"></a><section id=\"variant.Second\""));
+        assert!(!html.contains("//apple_ref/cpp/Variant/Second.field.value"));
+    }
+
+    #[test]
+    fn test_insert_dash_anchors_leaves_other_pages_untouched() {
+        let html = read_fixture("fixture/fn.my_function.html");
+        assert_eq!(insert_dash_anchors(&html), html);
+    }
+}
@@ -0,0 +1,239 @@
+//! Docset settings read from the `[package.metadata.docset]` and `[workspace.metadata.docset]`
+//! tables of the manifests.
+//!
+//! Settings given on the command line take precedence over the package settings, which take
+//! precedence over the workspace ones.
+
+use crate::{error::*, DocsetParams, Split};
+
+use cargo_metadata::{Metadata, Package};
+use serde::Deserialize;
+use snafu::ResultExt;
+
+/// The settings of a `docset` metadata table. Keys are named after the command line options.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DocsetConfig {
+    docset_name: Option<String>,
+    docset_index: Option<String>,
+    platform_family: Option<String>,
+    features: Option<Vec<String>>,
+    all_features: Option<bool>,
+    no_default_features: Option<bool>,
+    no_deps: Option<bool>,
+    document_private_items: Option<bool>,
+    targets: Option<Vec<String>>,
+    docs_rs: Option<bool>,
+    deps_depth: Option<usize>,
+    exclude_deps: Option<Vec<String>>,
+    feed_url: Option<String>,
+    contribution_author: Option<String>,
+    contribution_author_link: Option<String>,
+    contribution_aliases: Option<Vec<String>>,
+    split: Option<Split>
+}
+
+impl DocsetConfig {
+    /// Parse the `docset` table of a metadata table, if there is one.
+    fn parse(metadata: &serde_json::Value, table: &str) -> Result<DocsetConfig> {
+        match metadata.get("docset") {
+            Some(docset) => DocsetConfig::deserialize(docset).context(ConfigSnafu { table }),
+            None => Ok(DocsetConfig::default())
+        }
+    }
+
+    /// Use the settings of `other` for the ones which are not set in `self`.
+    fn or(self, other: DocsetConfig) -> DocsetConfig {
+        DocsetConfig {
+            docset_name: self.docset_name.or(other.docset_name),
+            docset_index: self.docset_index.or(other.docset_index),
+            platform_family: self.platform_family.or(other.platform_family),
+            features: self.features.or(other.features),
+            all_features: self.all_features.or(other.all_features),
+            no_default_features: self.no_default_features.or(other.no_default_features),
+            no_deps: self.no_deps.or(other.no_deps),
+            document_private_items: self.document_private_items.or(other.document_private_items),
+            targets: self.targets.or(other.targets),
+            docs_rs: self.docs_rs.or(other.docs_rs),
+            deps_depth: self.deps_depth.or(other.deps_depth),
+            exclude_deps: self.exclude_deps.or(other.exclude_deps),
+            feed_url: self.feed_url.or(other.feed_url),
+            contribution_author: self.contribution_author.or(other.contribution_author),
+            contribution_author_link: self.contribution_author_link.or(other.contribution_author_link),
+            contribution_aliases: self.contribution_aliases.or(other.contribution_aliases),
+            split: self.split.or(other.split)
+        }
+    }
+
+    /// Use these settings for the ones which were not given on the command line.
+    fn apply(self, cfg: &mut DocsetParams) {
+        cfg.docset_name = cfg.docset_name.take().or(self.docset_name);
+        cfg.docset_index = cfg.docset_index.take().or(self.docset_index);
+        cfg.platform_family = cfg.platform_family.take().or(self.platform_family);
+        if cfg.features.features.is_empty() {
+            cfg.features.features = self.features.unwrap_or_default();
+        }
+        cfg.features.all_features = flag(cfg.features.all_features, cfg.no_all_features, self.all_features);
+        cfg.features.no_default_features =
+            flag(cfg.features.no_default_features, cfg.default_features, self.no_default_features);
+        cfg.no_dependencies = flag(cfg.no_dependencies, cfg.dependencies, self.no_deps);
+        cfg.doc_private_items = flag(cfg.doc_private_items, cfg.no_doc_private_items, self.document_private_items);
+        if cfg.target.is_empty() {
+            cfg.target = self.targets.unwrap_or_default();
+        }
+        cfg.docs_rs = flag(cfg.docs_rs, cfg.no_docs_rs, self.docs_rs);
+        cfg.deps_depth = cfg.deps_depth.or(self.deps_depth);
+        if cfg.exclude_deps.is_empty() {
+            cfg.exclude_deps = self.exclude_deps.unwrap_or_default();
+        }
+        cfg.feed_url = cfg.feed_url.take().or(self.feed_url);
+        cfg.contribution_author = cfg.contribution_author.take().or(self.contribution_author);
+        cfg.contribution_author_link = cfg.contribution_author_link.take().or(self.contribution_author_link);
+        if cfg.contribution_alias.is_empty() {
+            cfg.contribution_alias = self.contribution_aliases.unwrap_or_default();
+        }
+        if cfg.split == Split::None {
+            cfg.split = self.split.unwrap_or_default();
+        }
+    }
+}
+
+/// Return the value of a flag which can be enabled or disabled on the command line, and otherwise
+/// takes the value of a setting.
+fn flag(enabled: bool, disabled: bool, setting: Option<bool>) -> bool {
+    enabled || (!disabled && setting.unwrap_or_default())
+}
+
+/// Return the package whose settings apply: the one that was selected if there is a single one,
+/// and the root package otherwise.
+pub fn get_config_package<'m>(cfg: &DocsetParams, metadata: &'m Metadata) -> Option<&'m Package> {
+    match (cfg.workspace.all || cfg.workspace.workspace, cfg.workspace.package.as_slice()) {
+        (false, [package]) => metadata.packages.iter().find(|p| &p.name == package),
+        _ => metadata.root_package()
+    }
+}
+
+/// Default the parameters which were not given on the command line from the docset metadata
+/// tables of the workspace and of the documented package.
+pub fn apply_metadata_config(cfg: &mut DocsetParams, metadata: &Metadata) -> Result<()> {
+    let workspace_config = DocsetConfig::parse(&metadata.workspace_metadata, "[workspace.metadata.docset]")?;
+    let package_config = match get_config_package(cfg, metadata) {
+        Some(package) => DocsetConfig::parse(
+            &package.metadata,
+            &format!("[package.metadata.docset] of package '{}'", package.name)
+        )?,
+        None => DocsetConfig::default()
+    };
+    package_config.or(workspace_config).apply(cfg);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use serde_json::json;
+
+    use super::DocsetConfig;
+    use crate::{Cli, Commands, DocsetParams, Split};
+
+    #[test]
+    fn test_parse_docset_config() {
+        let metadata = json!({
+            "docset": {
+                "docset-name": "My docset",
+                "features": ["a", "b"],
+                "no-deps": true,
+                "targets": ["wasm32-unknown-unknown"],
+                "split": "members",
+                "exclude-deps": ["windows-*", "*-sys"]
+            }
+        });
+        let config = DocsetConfig::parse(&metadata, "test").unwrap();
+        assert_eq!(config.docset_name.as_deref(), Some("My docset"));
+        assert_eq!(config.features, Some(vec!["a".to_owned(), "b".to_owned()]));
+        assert_eq!(config.no_deps, Some(true));
+        assert_eq!(config.targets, Some(vec!["wasm32-unknown-unknown".to_owned()]));
+        assert_eq!(config.split, Some(Split::Members));
+        assert_eq!(config.exclude_deps, Some(vec!["windows-*".to_owned(), "*-sys".to_owned()]));
+
+        assert!(DocsetConfig::parse(&json!(null), "test").unwrap().docset_name.is_none());
+        assert!(DocsetConfig::parse(&json!({"other-tool": {}}), "test").unwrap().docset_name.is_none());
+    }
+
+    #[test]
+    fn test_parse_docset_config_unknown_key() {
+        let metadata = json!({ "docset": { "docset-nmae": "Typo" } });
+        let err = DocsetConfig::parse(&metadata, "[workspace.metadata.docset]").unwrap_err().to_string();
+        assert!(err.contains("[workspace.metadata.docset]"), "Unexpected error {}", err);
+        assert!(err.contains("unknown field `docset-nmae`"), "Unexpected error {}", err);
+    }
+
+    #[test]
+    fn test_docset_config_precedence() {
+        let workspace = DocsetConfig::parse(
+            &json!({ "docset": { "docset-name": "Workspace", "platform-family": "ws", "no-deps": true } }),
+            "workspace"
+        )
+        .unwrap();
+        let package = DocsetConfig::parse(
+            &json!({ "docset": { "platform-family": "pkg", "no-deps": false, "features": ["a"] } }),
+            "package"
+        )
+        .unwrap();
+        let mut cfg = DocsetParams {
+            docset_name: Some("Command line".to_owned()),
+            ..Default::default()
+        };
+        package.or(workspace).apply(&mut cfg);
+
+        assert_eq!(cfg.docset_name.as_deref(), Some("Command line"));
+        assert_eq!(cfg.platform_family.as_deref(), Some("pkg"));
+        assert!(!cfg.no_dependencies);
+        assert_eq!(cfg.features.features, vec!["a"]);
+    }
+
+    fn docset_params(args: &[&str]) -> DocsetParams {
+        match Cli::try_parse_from(["cargo", "docset"].iter().chain(args)).unwrap().command {
+            Commands::Docset(params) => params
+        }
+    }
+
+    #[test]
+    fn test_docset_config_flags_can_be_disabled() {
+        let config = || {
+            DocsetConfig::parse(
+                &json!({ "docset": {
+                    "all-features": true,
+                    "no-default-features": true,
+                    "no-deps": true,
+                    "document-private-items": true,
+                    "docs-rs": true
+                } }),
+                "package"
+            )
+            .unwrap()
+        };
+
+        let mut cfg = docset_params(&[]);
+        config().apply(&mut cfg);
+        assert!(cfg.features.all_features && cfg.features.no_default_features);
+        assert!(cfg.no_dependencies && cfg.doc_private_items && cfg.docs_rs);
+
+        let mut cfg = docset_params(&[
+            "--no-all-features",
+            "--default-features",
+            "--deps",
+            "--no-document-private-items",
+            "--no-docs-rs"
+        ]);
+        config().apply(&mut cfg);
+        assert!(!cfg.features.all_features && !cfg.features.no_default_features);
+        assert!(!cfg.no_dependencies && !cfg.doc_private_items && !cfg.docs_rs);
+
+        // The last of the enabling and disabling flags wins.
+        let mut cfg = docset_params(&["--deps", "--no-deps", "--all-features", "--no-all-features"]);
+        config().apply(&mut cfg);
+        assert!(cfg.no_dependencies);
+        assert!(!cfg.features.all_features);
+    }
+}
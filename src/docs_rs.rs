@@ -0,0 +1,211 @@
+//! Build settings read from the `[package.metadata.docs.rs]` tables, which docs.rs uses to build
+//! the documentation of the crates it hosts.
+//!
+//! See <https://docs.rs/about/metadata> for the details.
+
+use crate::{error::*, io::*, DocsetParams};
+
+use cargo_metadata::{Metadata, Package};
+use serde::Deserialize;
+use snafu::ResultExt;
+
+use std::collections::HashMap;
+
+/// The settings of a `docs.rs` metadata table. Settings which do not affect the generated
+/// documentation are ignored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct DocsRsMetadata {
+    features: Vec<String>,
+    all_features: bool,
+    no_default_features: bool,
+    default_target: Option<String>,
+    targets: Vec<String>,
+    rustc_args: Vec<String>,
+    rustdoc_args: Vec<String>,
+    cargo_args: Vec<String>
+}
+
+impl DocsRsMetadata {
+    /// Parse the `docs.rs` metadata table of a package, if there is one.
+    fn parse(package: &Package) -> Result<Option<DocsRsMetadata>> {
+        match package.metadata.get("docs").and_then(|docs| docs.get("rs")) {
+            Some(docs_rs) => DocsRsMetadata::deserialize(docs_rs).map(Some).context(ConfigSnafu {
+                table: format!("[package.metadata.docs.rs] of package '{}'", package.name)
+            }),
+            None => Ok(None)
+        }
+    }
+
+    /// Return the target docs.rs uses for the main documentation page, if any.
+    fn default_target(&self) -> Option<&String> {
+        self.default_target.as_ref().or_else(|| self.targets.first())
+    }
+
+    /// Add these settings to the parameters of the `cargo doc` invocation.
+    fn apply(self, package_name: &str, cfg: &mut DocsetParams) {
+        // Several packages may be documented, so the features are qualified with their package.
+        for feature in &self.features {
+            let feature = format!("{}/{}", package_name, feature);
+            if !cfg.features.features.contains(&feature) {
+                cfg.features.features.push(feature);
+            }
+        }
+        cfg.features.all_features |= self.all_features && !cfg.no_all_features;
+        cfg.features.no_default_features |= self.no_default_features && !cfg.default_features;
+        // Packages of a workspace often share the same settings.
+        for (args, flags) in [
+            (self.rustdoc_args, &mut cfg.cargo.rustdoc_flags),
+            (self.rustc_args, &mut cfg.cargo.rustc_flags),
+            (self.cargo_args, &mut cfg.cargo.args)
+        ] {
+            extend_args(flags, &args);
+        }
+    }
+}
+
+/// Split arguments into groups of an option and its values, e.g. `--cfg docsrs`.
+fn arg_groups(args: &[String]) -> Vec<&[String]> {
+    let mut groups = vec![];
+    let mut start = 0;
+    for (i, arg) in args.iter().enumerate().skip(1) {
+        if arg.starts_with('-') {
+            groups.push(&args[start..i]);
+            start = i;
+        }
+    }
+    if start < args.len() {
+        groups.push(&args[start..]);
+    }
+    groups
+}
+
+/// Add the arguments of a package to the ones of the previous packages. The argument groups the
+/// previous packages already passed are not repeated, but the ones a package repeats are kept.
+fn extend_args(flags: &mut Vec<String>, args: &[String]) {
+    let mut passed = HashMap::new();
+    for group in arg_groups(flags) {
+        *passed.entry(group.to_vec()).or_insert(0) += 1;
+    }
+    for group in arg_groups(args) {
+        match passed.get_mut(group) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => flags.extend_from_slice(group)
+        }
+    }
+}
+
+/// Apply the docs.rs build settings of the documented packages to the `cargo doc` invocation. The
+/// target docs.rs uses for the main page is only used if no target was given.
+pub fn apply_docs_rs_metadata(cfg: &mut DocsetParams, metadata: &Metadata) -> Result<()> {
+    // docs.rs always builds the documentation with the `docsrs` cfg set.
+    extend_args(&mut cfg.cargo.rustdoc_flags, &["--cfg".to_owned(), "docsrs".to_owned()]);
+    let (included, _excluded) = cfg.workspace.partition_packages(metadata);
+    let set_target = cfg.target.is_empty();
+    let mut feature_flags = vec![];
+    for package in included {
+        let docs_rs = DocsRsMetadata::parse(package)?.unwrap_or_default();
+        feature_flags.push((docs_rs.all_features, docs_rs.no_default_features));
+        if set_target && cfg.target.is_empty() {
+            cfg.target.extend(docs_rs.default_target().cloned());
+        }
+        docs_rs.apply(&package.name, cfg);
+    }
+    // The feature flags apply to every documented package.
+    let all_features_differ = feature_flags.windows(2).any(|w| w[0].0 != w[1].0);
+    let no_default_features_differ = feature_flags.windows(2).any(|w| w[0].1 != w[1].1);
+    for (flag, differ) in [("all-features", all_features_differ), ("no-default-features", no_default_features_differ)] {
+        if differ {
+            warn(&format!(
+                "the documented packages disagree on `{}` in their docs.rs settings, it is enabled for all of them.",
+                flag
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::{apply_docs_rs_metadata, extend_args, DocsRsMetadata};
+    use crate::{commands::generate::tests::fixture_metadata, DocsetParams};
+
+    fn parse(metadata: serde_json::Value) -> DocsRsMetadata {
+        DocsRsMetadata::deserialize(&metadata).unwrap()
+    }
+
+    #[test]
+    fn test_apply_docs_rs_metadata() {
+        let docs_rs = parse(json!({
+            "features": ["serde"],
+            "no-default-features": true,
+            "targets": ["x86_64-pc-windows-msvc", "x86_64-unknown-linux-gnu"],
+            "rustdoc-args": ["--cfg", "docsrs"],
+            "rustc-args": ["--cfg", "my_cfg"],
+            "cargo-args": ["-Zbuild-std"],
+            // Settings which do not affect the documentation are ignored.
+            "proc-macro": false
+        }));
+        assert_eq!(docs_rs.default_target().unwrap(), "x86_64-pc-windows-msvc");
+
+        let mut cfg = DocsetParams::default();
+        docs_rs.apply("my-crate", &mut cfg);
+        assert_eq!(cfg.features.features, vec!["my-crate/serde"]);
+        assert!(cfg.features.no_default_features);
+        assert!(!cfg.features.all_features);
+        assert_eq!(cfg.cargo.rustdoc_flags, vec!["--cfg", "docsrs"]);
+        assert_eq!(cfg.cargo.rustc_flags, vec!["--cfg", "my_cfg"]);
+        assert_eq!(cfg.cargo.args, vec!["-Zbuild-std"]);
+
+        // The flags shared by several packages are only passed once.
+        parse(json!({ "rustdoc-args": ["--cfg", "docsrs"], "all-features": true })).apply("other", &mut cfg);
+        assert_eq!(cfg.cargo.rustdoc_flags, vec!["--cfg", "docsrs"]);
+        assert!(cfg.features.all_features);
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_extend_args() {
+        let mut flags = args(&["--cfg", "docsrs"]);
+        extend_args(&mut flags, &args(&["--cfg", "docsrs", "--cfg", "x", "--cfg", "x"]));
+        assert_eq!(flags, args(&["--cfg", "docsrs", "--cfg", "x", "--cfg", "x"]));
+        extend_args(&mut flags, &args(&["--cfg", "x", "-Zunstable-options", "--cfg", "y"]));
+        assert_eq!(flags, args(&["--cfg", "docsrs", "--cfg", "x", "--cfg", "x", "-Zunstable-options", "--cfg", "y"]));
+        // Values are compared with their option.
+        let mut flags = args(&["--cfg", "x"]);
+        extend_args(&mut flags, &args(&["--crate-version", "x"]));
+        assert_eq!(flags, args(&["--cfg", "x", "--crate-version", "x"]));
+    }
+
+    #[test]
+    fn test_apply_docs_rs_metadata_sets_docsrs_cfg() {
+        let mut metadata = fixture_metadata();
+        let mut cfg = DocsetParams::default();
+        apply_docs_rs_metadata(&mut cfg, &metadata).unwrap();
+        assert_eq!(cfg.cargo.rustdoc_flags, args(&["--cfg", "docsrs"]));
+
+        // The cfg is not repeated when the docs.rs settings set it too.
+        let package = metadata.packages.iter_mut().find(|p| p.name == "a").unwrap();
+        package.metadata = json!({ "docs": { "rs": { "rustdoc-args": ["--cfg", "docsrs", "--cfg", "x"] } } });
+        let mut cfg = DocsetParams::default();
+        apply_docs_rs_metadata(&mut cfg, &metadata).unwrap();
+        assert_eq!(cfg.cargo.rustdoc_flags, args(&["--cfg", "docsrs", "--cfg", "x"]));
+    }
+
+    #[test]
+    fn test_docs_rs_default_target() {
+        assert_eq!(
+            parse(json!({ "default-target": "wasm32-unknown-unknown", "targets": ["x86_64-pc-windows-msvc"] }))
+                .default_target()
+                .unwrap(),
+            "wasm32-unknown-unknown"
+        );
+        assert!(parse(json!({})).default_target().is_none());
+    }
+}
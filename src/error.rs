@@ -9,10 +9,6 @@ pub enum Error {
     CargoDoc {
         code: Option<i32>
     },
-    #[snafu(display("Cargo clean exited with code {:?}.", code))]
-    CargoClean {
-        code: Option<i32>
-    },
     #[snafu(display("Error running 'cargo metadata' command: {}", source))]
     CargoMetadata {
         source: cargo_metadata::Error
@@ -33,10 +29,38 @@ pub enum Error {
     IoWrite {
         source: std::io::Error
     },
+    #[snafu(display("Could not parse the rustdoc search index: {}", msg))]
+    SearchIndex {
+        msg: &'static str
+    },
+    #[snafu(display("Could not parse the rustdoc search index: {}", source))]
+    SearchIndexJson {
+        source: serde_json::Error
+    },
+    #[snafu(display("Could not parse the rustdoc JSON output: {}", source))]
+    RustdocJson {
+        source: serde_json::Error
+    },
+    #[snafu(display("Unsupported rustdoc JSON format version {} in {}, expected version {}.",
+        version, path.display(), rustdoc_types::FORMAT_VERSION))]
+    RustdocJsonFormat {
+        path: std::path::PathBuf,
+        version: u64
+    },
     #[snafu(display("SQLite error {}", source))]
     Sqlite {
         source: rusqlite::Error
     },
+    #[snafu(display("Invalid docset configuration in {}: {}", table, source))]
+    Config {
+        table: String,
+        source: serde_json::Error
+    },
+    #[snafu(display("Invalid docset.json in {}: {}", path.display(), source))]
+    Contribution {
+        path: std::path::PathBuf,
+        source: serde_json::Error
+    },
     #[snafu(display("CLI arguments error: {}", msg))]
     Args {
         msg: &'static str
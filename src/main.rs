@@ -1,12 +1,17 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand, Args};
+use clap::{Parser, Subcommand, Args, ValueEnum};
+use serde::Deserialize;
 
+mod cargo;
 mod commands;
+mod config;
+mod docs_rs;
 mod error;
 mod io;
 
 use crate::error::*;
+use cargo::CargoOptions;
 use commands::generate::generate_docset;
 
 #[derive(Debug, Parser)]
@@ -15,6 +20,56 @@ struct Cli {
     command: Commands
 }
 
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq, Eq)]
+/// Where the docset entries are read from.
+pub enum EntrySource {
+    #[default]
+    /// Infer the entries from the names of the files generated by rustdoc.
+    Filenames,
+    /// Read the entries from the search index generated by rustdoc, and fall back to the file
+    /// names if there is none.
+    SearchIndex,
+    /// Read the entries from the rustdoc JSON output, which is generated in addition to the HTML
+    /// documentation. Requires a nightly toolchain.
+    RustdocJson
+}
+
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq, Eq)]
+/// Under which paths re-exported items are indexed.
+pub enum Reexports {
+    #[default]
+    /// Only index items under their canonical path.
+    Canonical,
+    /// Index re-exported items under their re-export path instead of their canonical path.
+    Reexport,
+    /// Index re-exported items under both their canonical and re-export paths.
+    Both
+}
+
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq, Eq)]
+/// How the docset entries are named in the docset browser.
+pub enum EntryNames {
+    #[default]
+    /// Name the entries with their fully qualified path, e.g. `my_crate::module::Item`.
+    Qualified,
+    /// Name the entries with their bare name, e.g. `Item`, and show their module path and crate
+    /// version in the description of the search results.
+    Short
+}
+
+#[derive(ValueEnum, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+/// Which docsets are generated from the documentation.
+pub enum Split {
+    #[default]
+    /// Generate a single docset containing the documentation of every documented crate.
+    None,
+    /// Generate one docset per documented workspace member.
+    Members,
+    /// Generate one docset per documented package, including dependencies.
+    Packages
+}
+
 #[derive(Args, Default, Debug, Clone)]
 /// Generate a docset. This is currently the only available command, and should remain the
 /// default one in the future if new ones are added.
@@ -25,20 +80,58 @@ pub struct DocsetParams {
     pub workspace: clap_cargo::Workspace,
     #[clap(flatten)]
     features: clap_cargo::Features,
+    #[clap(long, action, overrides_with("all_features"))]
+    /// Do not activate all the available features, even if the manifest settings do.
+    pub no_all_features: bool,
+    #[clap(long, action, overrides_with("no_default_features"))]
+    /// Activate the `default` feature, even if the manifest settings do not.
+    pub default_features: bool,
+    #[clap(flatten)]
+    pub cargo: CargoOptions,
     #[clap(long("no-deps"))]
     /// Do not document dependencies.
     pub no_dependencies: bool,
+    #[clap(long("deps"), action, overrides_with("no_dependencies"))]
+    /// Document dependencies, even if the manifest settings do not.
+    pub dependencies: bool,
     #[clap(long("document-private-items"))]
     /// Generate documentation for private items.
     pub doc_private_items: bool,
+    #[clap(long("no-document-private-items"), action, overrides_with("doc_private_items"))]
+    /// Do not generate documentation for private items, even if the manifest settings do.
+    pub no_doc_private_items: bool,
     #[clap(long, value_parser)]
-    /// Build documentation for the specified target triple.
-    pub target: Option<String>,
+    /// Build documentation for the specified target triple. This can be repeated to build
+    /// documentation for several targets, in which case one docset is generated per target.
+    pub target: Vec<String>,
+    #[clap(long, action)]
+    /// Generate a single docset merging the documentation built for all the targets, rather than
+    /// one docset per target. Entries are labeled with the targets they are available on.
+    pub merge_targets: bool,
+    #[clap(long, value_enum, default_value_t)]
+    /// Generate one docset per package rather than a single one. Each docset is named after its
+    /// package, which is also used as its platform family.
+    pub split: Split,
     #[clap(long, value_parser)]
     /// Override the workspace target directory.
     pub target_dir: Option<PathBuf>,
     #[clap(long, action)]
-    /// Do not clean the doc directory before generating the rustdoc.
+    /// Build the documentation with the settings of the `[package.metadata.docs.rs]` table of the
+    /// documented packages, like docs.rs does.
+    pub docs_rs: bool,
+    #[clap(long, action, overrides_with("docs_rs"))]
+    /// Do not apply the docs.rs settings, even if the manifest settings do.
+    pub no_docs_rs: bool,
+    #[clap(long, value_parser, value_name("N"))]
+    /// Only include the dependencies up to the given depth in the dependency graph, 1 meaning the
+    /// direct dependencies only. Links to the other ones point to docs.rs.
+    pub deps_depth: Option<usize>,
+    #[clap(long, value_parser, value_name("GLOB"))]
+    /// Leave the dependencies whose name matches the given pattern out of the docset, e.g.
+    /// `windows-*`. This can be repeated. Links to them point to docs.rs.
+    pub exclude_deps: Vec<String>,
+    #[clap(long, action)]
+    /// Do not clean the documentation of the documented crates before generating the rustdoc.
     pub no_clean: bool,
     #[clap(long, action)]
     /// Document only this package's library.
@@ -50,6 +143,27 @@ pub struct DocsetParams {
     /// Document all binaries.
     pub bins: bool,
     #[clap(long, value_parser)]
+    /// Document only the specified example.
+    pub example: Vec<String>,
+    #[clap(long, action)]
+    /// Document all examples.
+    pub examples: bool,
+    #[clap(long, action)]
+    /// Also package the docset as a `.tgz` archive, in the layout expected by Dash and Zeal.
+    pub archive: bool,
+    #[clap(long, action, conflicts_with("archive"))]
+    /// Only package the docset as a `.tgz` archive. The docset directory is still generated, and
+    /// removed once it is archived.
+    pub archive_only: bool,
+    #[clap(long, action)]
+    /// Store the documentation in a compressed and indexed tarball, which Dash reads without
+    /// extracting it. This makes large docsets much faster to copy and install.
+    pub tarix: bool,
+    #[clap(long, value_parser, value_name("BASE_URL"))]
+    /// Generate the Dash and JSON feeds of the docset archive, which is published under the given
+    /// URL. This implies `--archive`.
+    pub feed_url: Option<String>,
+    #[clap(long, value_parser)]
     /// Specify or override the name of the docset, this is the display name used by your docset
     /// browser.
     pub docset_name: Option<String>,
@@ -59,7 +173,33 @@ pub struct DocsetParams {
     #[clap(long, value_parser)]
     /// Specify or override the docset platform family, this is used as the keyword you can specify
     /// in your docset browser search bar to search this specific docset).
-    pub platform_family: Option<String>
+    pub platform_family: Option<String>,
+    #[clap(long, value_parser, value_name("DIR"))]
+    /// Generate the layout of a Dash user-contributed docset, in a directory named after the docset
+    /// in the given directory. Previously published versions are kept under `versions/`. This
+    /// implies `--archive`.
+    pub contribution_dir: Option<PathBuf>,
+    #[clap(long, value_parser, value_name("NAME"))]
+    /// Specify or override the author of the user-contributed docset, the first package author by
+    /// default.
+    pub contribution_author: Option<String>,
+    #[clap(long, value_parser, value_name("URL"))]
+    /// Specify or override the link to the author of the user-contributed docset, the package
+    /// repository by default.
+    pub contribution_author_link: Option<String>,
+    #[clap(long, value_parser, value_name("ALIAS"))]
+    /// Add an alias of the user-contributed docset. This can be repeated.
+    pub contribution_alias: Vec<String>,
+    #[clap(long, value_enum, default_value_t)]
+    /// Specify where the docset entries are read from.
+    pub entry_source: EntrySource,
+    #[clap(long, value_enum, default_value_t)]
+    /// Specify under which paths re-exported items are indexed. Re-exports are found in the
+    /// "Re-exports" sections of the module pages, and only with the file names entry source.
+    pub reexports: Reexports,
+    #[clap(long, value_enum, default_value_t)]
+    /// Specify how the docset entries are named in the docset browser.
+    pub entry_names: EntryNames
 }
 
 impl DocsetParams {
@@ -96,7 +236,7 @@ impl DocsetParams {
         if self.features.all_features {
             args.push("--all-features".to_owned())
         }
-        if let Some(target) = self.target {
+        for target in self.target {
             args.push("--target".to_owned());
             args.push(target);
         }
@@ -107,9 +247,19 @@ impl DocsetParams {
         if self.lib {
             args.push("--lib".to_owned());
         }
+        for bin in self.bin {
+            args.extend_from_slice(&["--bin".to_owned(), bin]);
+        }
         if self.bins {
-            args.push("bins".to_owned());
+            args.push("--bins".to_owned());
+        }
+        for example in self.example {
+            args.extend_from_slice(&["--example".to_owned(), example]);
+        }
+        if self.examples {
+            args.push("--examples".to_owned());
         }
+        args.extend(self.cargo.build_args());
         args
     }
 }
@@ -157,6 +307,13 @@ mod tests {
         assert!(args.is_empty());
     }
 
+    #[test]
+    fn test_archive_options_conflict() {
+        assert!(Cli::try_parse_from(["cargo", "docset", "--archive-only"]).is_ok());
+        let err = Cli::try_parse_from(["cargo", "docset", "--archive", "--archive-only"]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
     const TEST_DOCSET_PARAMS_1_MANIFEST_PATH: &str = "../somewhere_else/";
     const TEST_DOCSET_PARAMS_1_ENABLED_FEATURE: &str = "feature1";
     const TEST_DOCSET_PARAMS_1_EXCLUDED: &str = "excluded_package";
@@ -219,8 +376,7 @@ mod tests {
 
                 assert!(params.doc_private_items);
 
-                assert!(params.target.is_some());
-                assert_eq!(params.target.unwrap(), TEST_DOCSET_PARAMS_1_TARGET);
+                assert_eq!(params.target, vec![TEST_DOCSET_PARAMS_1_TARGET]);
 
                 assert!(params.target_dir.is_none());
 
@@ -232,6 +388,10 @@ mod tests {
 
                 assert!(!params.bins);
 
+                assert!(params.example.is_empty());
+
+                assert!(!params.examples);
+
                 assert!(params.docset_name.is_some());
                 assert_eq!(params.docset_name.unwrap(), TEST_DOCSET_PARAMS_1_DOCSET_NAME);
 
@@ -285,4 +445,73 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_multiple_targets_into_args() {
+        let cli = Cli::parse_from([
+            "cargo",
+            "docset",
+            "--target",
+            "x86_64-unknown-linux-gnu",
+            "--target",
+            "wasm32-unknown-unknown"
+        ]);
+        match cli.command {
+            Commands::Docset(params) => {
+                assert_eq!(params.target, vec!["x86_64-unknown-linux-gnu", "wasm32-unknown-unknown"]);
+                assert_eq!(params.into_args(), vec![
+                    "--target",
+                    "x86_64-unknown-linux-gnu",
+                    "--target",
+                    "wasm32-unknown-unknown"
+                ]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cargo_options_into_args() {
+        let cli = Cli::parse_from(["cargo", "docset", "--offline", "-Z", "rustdoc-map", "-j", "2", "--", "--timings"]);
+        match cli.command {
+            Commands::Docset(params) => {
+                assert_eq!(params.into_args(), vec!["--offline", "-Z", "rustdoc-map", "--jobs", "2", "--timings"]);
+            }
+        }
+    }
+
+    const TEST_DOCSET_PARAMS_2_ARGS: &[&str] = &[
+        "cargo",
+        "docset",
+        "--lib",
+        "--bin",
+        "first-bin",
+        "--bin",
+        "second-bin",
+        "--bins",
+        "--example",
+        "an-example",
+        "--examples"
+    ];
+
+    #[test]
+    fn test_validate_docset_params_2_into_args() {
+        let params = Cli::parse_from(TEST_DOCSET_PARAMS_2_ARGS);
+        match params.command {
+            Commands::Docset(params) => {
+                assert_eq!(params.bin, vec!["first-bin", "second-bin"]);
+                assert_eq!(params.example, vec!["an-example"]);
+                assert_eq!(params.into_args(), vec![
+                    "--lib",
+                    "--bin",
+                    "first-bin",
+                    "--bin",
+                    "second-bin",
+                    "--bins",
+                    "--example",
+                    "an-example",
+                    "--examples"
+                ]);
+            }
+        }
+    }
 }
@@ -0,0 +1,2 @@
+window.ALL_CRATES = ["fixture","std"];
+//{"start":21,"fragment_lengths":[9,6]}
@@ -0,0 +1 @@
+window.SIDEBAR_ITEMS = {"attr":["my_attribute"],"constant":["MY_CONST"],"derive":["MyDerive"],"enum":["MyEnum"],"fn":["my_function"],"foreigntype":["MyForeignType"],"macro":[["my_macro",1]],"mod":["inner"],"static":["MY_STATIC"],"struct":["Hidden","MyStruct"],"trait":["MyTrait"],"traitalias":["MyTraitAlias"],"type":["MyType"],"union":["MyUnion"]};
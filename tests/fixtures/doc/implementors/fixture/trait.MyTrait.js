@@ -0,0 +1,3 @@
+(function() {var implementors = {};
+implementors["other"] = [{"text":"impl <a class=\"trait\" href=\"fixture/trait.MyTrait.html\" title=\"trait fixture::MyTrait\">MyTrait</a> for <a class=\"struct\" href=\"other/struct.Other.html\" title=\"struct other::Other\">Other</a>","synthetic":false,"types":["other::Other"]}];
+if (window.register_implementors) {window.register_implementors(implementors);} else {window.pending_implementors = implementors;}})()
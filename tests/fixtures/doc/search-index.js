@@ -0,0 +1,6 @@
+var searchIndex = new Map(JSON.parse('[\
+["fixture",{"t":"SJGFKVZIUYXHQCPPOONTRNNNRMNOOFF","n":["MY_CONST","MY_STATIC","MyEnum","MyStruct","MyTrait","MyForeignType","MyTraitAlias","MyType","MyUnion","MyDerive","my_attribute","my_function","my_macro","inner","First","Second","value","field","new","ZERO","Output","required","clone","clone_from","Output","required","provided","int","float","Hidden","Inner"],"q":[[0,"fixture"],[30,"fixture::inner"]],"i":"``````````````bbhdddddddfffjj``","f":"","p":[[6,"MyEnum"],[5,"MyStruct"],[10,"MyTrait"],[15,"Second"],[20,"MyUnion"]],"a":{"my_alias":[3],"create":[18]}}],\
+["std",{"t":"ABTNN","n":["match","u8","MAX","is_ascii","add"],"q":[[0,"std"]],"i":"``bbb","f":"","p":[[1,"u8"]],"a":{}}]\
+]'));
+if (typeof exports !== 'undefined') exports.searchIndex = searchIndex;
+else if (window.initSearch) window.initSearch(searchIndex);
@@ -0,0 +1,81 @@
+//! Fixture crate for the `rustdoc-json` entry source tests.
+//!
+//! `fixture.json` is generated from this file with
+//! `RUSTDOCFLAGS="-Z unstable-options --output-format json" cargo +nightly doc`, keeping only the
+//! local entries of `paths`.
+#![feature(extern_types, trait_alias)]
+
+/// A constant.
+pub const MY_CONST: u8 = 0;
+/// A static.
+pub static MY_STATIC: u8 = 0;
+
+/// An enum.
+pub enum MyEnum {
+    First,
+    Second { value: u8 }
+}
+
+/// A struct.
+#[doc(alias = "my_alias")]
+#[derive(Clone)]
+pub struct MyStruct {
+    pub field: u8
+}
+
+impl MyStruct {
+    /// Create a new instance.
+    #[doc(alias = "create")]
+    pub fn new() -> Self {
+        MyStruct { field: 0 }
+    }
+
+    pub const ZERO: Self = MyStruct { field: 0 };
+
+    /// A deprecated method.
+    #[deprecated]
+    pub fn old(&self) {}
+}
+
+/// A trait.
+pub trait MyTrait {
+    type Output;
+    fn required(&self);
+    fn provided(&self) {}
+}
+
+impl MyTrait for MyStruct {
+    type Output = u8;
+    fn required(&self) {}
+}
+
+/// A trait alias.
+pub trait MyTraitAlias = MyTrait;
+
+/// A type alias.
+pub type MyType = MyStruct;
+
+/// A union.
+pub union MyUnion {
+    pub int: u32,
+    pub float: f32
+}
+
+extern "C" {
+    /// A foreign type.
+    pub type MyForeignType;
+}
+
+/// A function.
+pub fn my_function() {}
+
+/// A macro.
+#[macro_export]
+macro_rules! my_macro {
+    () => {};
+}
+
+pub mod inner {
+    /// A struct in a module.
+    pub struct Inner;
+}